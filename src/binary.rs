@@ -1,90 +1,80 @@
+use typst_syntax::ast::AstNode;
+
 use super::*;
 
-/// only format tight cause it would not be supported to format breaking in code blocks
+/// formats binary operations, they may only break before their operators when
+/// they're in parenthesis, a newline would end the expression in code blocks.
 ///
-/// it could be supported in parenthesized.
+/// Operations with the same precedence break together: `a + b + c`.
 #[instrument(skip_all, ret)]
-pub(crate) fn format_bin_left_assoc(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let res = format_bin_left_assoc_tight(parent, children, ctx);
-
-    if parent.kind() == Parenthesized
-        && (
-            crate::utils::max_line_length(&res) >= ctx.config.max_line_length
-            // || parent
-            // .children()
-            // .any(|x| x.kind() == LineComment || x.kind() == BlockComment)
-        )
-    {
-        return format_bin_left_assoc_breaking(parent, children, ctx);
-    }
-    res
-}
-
-/// not integrated and never used for now
-#[instrument(skip_all)]
-pub(crate) fn format_bin_left_assoc_breaking(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+pub(crate) fn format_bin_left_assoc(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let breakable = in_parenthesis(parent);
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
-            x if BinOp::from_kind(x).is_some() => {
-                ctx.push_in("\n", &mut res);
-                ctx.push_raw_indent(s, &mut res);
-            }
-            LineComment | BlockComment => {
-                ctx.push_raw_indent(s, &mut res);
-                ctx.push_in("\n", &mut res);
-            }
-            Space => {}
-            _ => {
-                ctx.push_in(s, &mut res);
+            // handles `not in` like a pro
+            In if utils::prev_is_ignoring(&node, Not, &[Space]) => {
+                ctx.push_in(" ", &mut res);
+                ctx.push_doc(s, &mut res);
+                ctx.push_in(" ", &mut res);
             }
-        }
-    }
-    res
-}
-
-#[instrument(skip_all)]
-pub(crate) fn format_bin_left_assoc_tight(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
-        match node.kind() {
-            _ if ctx.off => res.push_str(node.text()),
-
             x if BinOp::from_kind(x).is_some() => {
-                ctx.push_in(" ", &mut res);
-                ctx.push_raw_in(s, &mut res);
+                push_separator(breakable, &mut res, ctx);
+                ctx.push_doc(s, &mut res);
                 ctx.push_in(" ", &mut res);
             }
-            // handles `not in` like a pro
             Not => {
-                ctx.push_in(" ", &mut res);
-                ctx.push_raw_in(s, &mut res);
+                push_separator(breakable, &mut res, ctx);
+                ctx.push_doc(s, &mut res);
             }
             Space => {}
             LineComment => {
                 ctx.push_in(" ", &mut res);
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 ctx.push_in("\n", &mut res);
                 ctx.push_raw_in(&ctx.get_indent(), &mut res);
                 ctx.just_spaced = true;
             }
             _ => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
+            }
+        }
+    }
+
+    let same_precedence_as_parent = parent
+        .parent()
+        .and_then(|outer| typst_syntax::ast::Binary::from_untyped(outer))
+        .zip(typst_syntax::ast::Binary::from_untyped(parent))
+        .is_some_and(|(outer, inner)| outer.op().precedence() == inner.op().precedence());
+    if breakable && !same_precedence_as_parent {
+        Doc::group(Doc::concat(res))
+    } else {
+        Doc::concat(res)
+    }
+}
+
+/// the space before an operator, a line if the operation is breakable.
+fn push_separator(breakable: bool, res: &mut Vec<Doc>, ctx: &mut Ctx) {
+    if !breakable {
+        ctx.push_in(" ", res);
+    } else if !(ctx.just_spaced || res.ends_with(' ')) {
+        res.push(Doc::Line);
+        ctx.just_spaced = true;
+    }
+}
+
+/// tells if the operation is directly in parenthesis, maybe nested in other operations.
+fn in_parenthesis(node: &LinkedNode) -> bool {
+    let mut node = node.clone();
+    loop {
+        match node.parent() {
+            Some(parent) if parent.kind() == Binary => {
+                let parent = parent.clone();
+                node = parent;
             }
+            Some(parent) => return parent.kind() == Parenthesized,
+            None => return false,
         }
     }
-    res
 }
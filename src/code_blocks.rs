@@ -2,14 +2,12 @@ use super::*;
 use crate::utils::{get_next_ignoring, next_is_ignoring};
 
 #[instrument(skip_all)]
-/// format code blocks using [format_code_blocks_breaking] or [format_code_blocks_tight]
-/// depending on the context.
-pub(crate) fn format_code_blocks(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let children_contains_lines = children.iter().any(|c| c.contains('\n'));
+/// format code blocks on one line if they fit, breaking after the opening brace otherwise.
+///
+/// They always break if one of their children contains a newline or if
+/// they're the body of a loop.
+pub(crate) fn format_code_blocks(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let children_contains_lines = children.iter().any(Doc::has_newline);
     let parent_is_loop = [Some(ForLoop), Some(WhileLoop)].contains(&parent.parent_kind());
     let code = utils::find_child(parent, &|x| x.kind() == Code).unwrap();
 
@@ -21,34 +19,47 @@ pub(crate) fn format_code_blocks(
         return format_code_blocks_tight(parent, children, ctx);
     }
 
-    if children_contains_lines || parent_is_loop {
-        debug!("format breaking cause: children contains breakline: {children_contains_lines}");
-        debug!("or because parent is loop: {parent_is_loop}");
-        return format_code_blocks_breaking(parent, children, ctx);
+    if parent.children().any(|c| c.kind() == BlockComment) {
+        // comments aren't placed the same way when breaking.
+        let tight = format_code_blocks_tight(parent, children.clone(), ctx);
+        let breaking = format_code_blocks_breaking(parent, children, ctx);
+        return Doc::group(Doc::if_break(breaking, tight));
     }
 
-    let res = format_code_blocks_tight(parent, children, ctx);
-    let max_line_length = utils::max_line_length(&res);
+    let line = if children_contains_lines || parent_is_loop {
+        debug!("format breaking cause: children contains breakline: {children_contains_lines}");
+        debug!("or because parent is loop: {parent_is_loop}");
+        Doc::HardLine
+    } else {
+        Doc::Line
+    };
 
-    if max_line_length >= ctx.config.max_line_length {
-        debug!(
-            "format breaking cause max_line_length ({}) above limit",
-            max_line_length
-        );
-        return format_code_blocks_breaking(parent, children, ctx);
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            LeftBrace => ctx.push_doc(s, &mut res),
+            RightBrace => {
+                res.push(line.clone());
+                ctx.push_doc(s, &mut res);
+            }
+            Space => {}
+            _ => {
+                ctx.push_doc_indent(Doc::concat(vec![line.clone(), s]), &mut res);
+            }
+        }
     }
-    debug!("format tight");
-    res
+    Doc::group(Doc::concat(res))
 }
 
 #[instrument(skip_all)]
 pub(crate) fn format_code_blocks_tight(
     parent: &LinkedNode,
-    children: &[String],
+    children: Vec<Doc>,
     ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             LeftBrace => {
@@ -62,38 +73,39 @@ pub(crate) fn format_code_blocks_tight(
                     break;
                 }
                 debug!("leftbrace formatted not empty!");
-                res.push_str(s);
-                res.push(' ');
+                res.push(s);
+                res.push_str(" ");
             }
             RightBrace => {
-                res.push(' ');
-                res.push_str(s);
+                res.push_str(" ");
+                res.push(s);
             }
             Space => {}
             _ => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
         }
     }
-    res
+    Doc::concat(res)
 }
 
-#[instrument(skip_all, ret)]
+#[instrument(skip_all)]
 pub(crate) fn format_code_blocks_breaking(
     parent: &LinkedNode,
-    children: &[String],
+    children: Vec<Doc>,
     ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(&deep_no_format(&node)),
             LeftBrace => {
-                res.push_str(&format!("{s}\n{}", ctx.get_indent()));
+                res.push(s);
+                res.push_str(&format!("\n{}", ctx.get_indent()));
                 ctx.just_spaced = true;
             }
             LineComment | BlockComment => {
-                let buf = format_comment_handling_disable(&node, &[], ctx);
+                let buf = format_comment_handling_disable(&node, ctx);
                 if ctx.off {
                     ctx.push_raw_in(&buf, &mut res);
                     continue;
@@ -101,22 +113,21 @@ pub(crate) fn format_code_blocks_breaking(
                 if utils::prev_is_ignoring(&node, LineComment, &[Space])
                     || utils::prev_is_ignoring(&node, BlockComment, &[Space])
                 {
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                     if !utils::next_is_ignoring(&node, RightBrace, &[Space]) {
                         ctx.push_in("\n", &mut res);
                         ctx.consec_new_line = 2;
                     }
                 } else {
                     let prev = node.prev_sibling().unwrap();
-                    let mark = res.rfind(|x| x != ' ' && x != '\n').unwrap() + 1;
                     let prev_maybe_space = get_next_ignoring(&prev, &[]);
                     // go back before
-                    res = res[..mark].to_string();
+                    doc::trim_end(&mut res);
                     match prev_maybe_space {
                         Some(space) if space.kind() == Space && space.text().contains('\n') => {
-                            res.push('\n');
+                            res.push_str("\n");
                             res.push_str(&ctx.get_indent());
-                            res.push_str(s.trim_start());
+                            res.push_str(buf.trim_start());
 
                             if !utils::next_is_ignoring(&node, RightBrace, &[Space]) {
                                 ctx.push_in("\n", &mut res);
@@ -125,8 +136,8 @@ pub(crate) fn format_code_blocks_breaking(
                             }
                         }
                         _ => {
-                            res.push(' ');
-                            res.push_str(s);
+                            res.push_str(" ");
+                            res.push(s);
                             if !utils::next_is_ignoring(&node, RightBrace, &[Space]) {
                                 ctx.push_in("\n", &mut res);
                                 ctx.consec_new_line = 2;
@@ -141,14 +152,14 @@ pub(crate) fn format_code_blocks_breaking(
             }
             RightBrace => {
                 ctx.push_in("\n", &mut res);
-                res.push_str(s);
+                res.push(s);
             }
             Space => {}
             _ => {
-                ctx.push_raw_indent(s, &mut res);
+                ctx.push_doc_indent(s, &mut res);
             }
         }
     }
     ctx.lost_context();
-    res
+    Doc::concat(res)
 }
@@ -1,14 +1,157 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
 use serde::Deserialize;
 use serde::Serialize;
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(default)]
-#[serde(deny_unknown_fields)]
-pub struct Config {
-    pub indent_space: usize,
-    pub max_line_length: usize,
-    /// If enabled, when breaking arguments, it will try to keep more on one line.
-    pub experimental_args_breaking_consecutive: bool,
-    pub line_wrap: bool,
+use toml::Spanned;
+
+use crate::schema::{setting_enum, settings, SettingSchema};
+use crate::{pragma, utils, ConfigError};
+
+settings! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    #[serde(deny_unknown_fields)]
+    pub struct Config {
+        /// The width of an indentation level, with tabs, the width of a tab.
+        pub indent_space: usize,
+        /// Whether indentation is made of tabs or spaces.
+        pub indent_style: IndentStyle,
+        /// The width lines are kept under when possible.
+        pub max_line_length: usize,
+        /// If enabled, when breaking arguments, it will try to keep more on one line.
+        pub experimental_args_breaking_consecutive: bool,
+        /// If enabled, text in markup is wrapped at [Config::max_line_length].
+        pub line_wrap: bool,
+        /// If enabled, the output is parsed again and compared to the input,
+        /// if the formatting would change the meaning of the document it is left untouched.
+        pub verify: bool,
+        /// The line endings of the output, `auto` keeps the one most used in the input.
+        pub end_of_line: EndOfLine,
+        /// The style edition, changes to the formatting are only made in a new edition
+        /// so that upgrading typstfmt doesn't change the output until it is opted in.
+        pub edition: Edition,
+    }
+}
+
+setting_enum! {
+    /// What indentation is made of.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum IndentStyle {
+        /// [Config::indent_space] spaces by level.
+        #[default]
+        Space,
+        /// A tab by level, [Config::indent_space] being its width when measuring lines.
+        Tab,
+    }
+}
+
+setting_enum! {
+    /// Which line endings the output uses.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum EndOfLine {
+        /// The line ending most used in the input, `\n` if it has no line breaks.
+        #[default]
+        Auto,
+        /// `\n`
+        Lf,
+        /// `\r\n`
+        Crlf,
+    }
+}
+
+/// A version of the formatting style, see [Config::edition].
+///
+/// Formatting changes are gated with `ctx.config.edition >= Edition::E20XX`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "EditionName")]
+pub enum Edition {
+    /// The style when editions were introduced, including the changes made before them.
+    #[default]
+    #[serde(rename = "2023")]
+    E2023,
+    /// `#let f(x) = x` is spaced around its `=`.
+    #[serde(rename = "2024")]
+    E2024,
+}
+
+impl Edition {
+    /// every edition, in order.
+    const ALL: [Edition; 2] = [Edition::E2023, Edition::E2024];
+
+    /// the year naming the edition.
+    fn name(self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|name| name.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+}
+
+/// an edition is written as an integer or a string, the schema has both.
+impl SettingSchema for Edition {
+    fn schema() -> serde_json::Value {
+        let names = Edition::ALL.map(Edition::name);
+        let years = names
+            .clone()
+            .map(|name| name.parse::<u64>().unwrap_or_default());
+        serde_json::json!({
+            "oneOf": [
+                { "type": "integer", "enum": years },
+                { "type": "string", "enum": names },
+            ],
+        })
+    }
+}
+
+/// an edition is written `2024` or `"2024"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EditionName {
+    Year(u64),
+    Name(String),
+}
+
+impl TryFrom<EditionName> for Edition {
+    type Error = String;
+
+    fn try_from(name: EditionName) -> Result<Self, Self::Error> {
+        let name = match name {
+            EditionName::Year(year) => year.to_string(),
+            EditionName::Name(name) => name,
+        };
+        Edition::ALL
+            .into_iter()
+            .find(|edition| edition.name() == name)
+            .ok_or_else(|| {
+                let names = Edition::ALL.map(|edition| format!("`{}`", edition.name()));
+                format!("unknown edition `{name}`, expected {}", names.join(" or "))
+            })
+    }
+}
+
+impl EndOfLine {
+    /// The line ending to use for the output of `input`.
+    pub(crate) fn resolve(self, input: &str) -> &'static str {
+        match self {
+            EndOfLine::Lf => "\n",
+            EndOfLine::Crlf => "\r\n",
+            EndOfLine::Auto => {
+                let crlf = input.matches("\r\n").count();
+                let lf = input.matches('\n').count() - crlf;
+                if crlf > lf {
+                    "\r\n"
+                } else {
+                    "\n"
+                }
+            }
+        }
+    }
 }
 
 impl Default for Config {
@@ -16,19 +159,387 @@ impl Default for Config {
         Self {
             // this being strictly > to 1 is assumed.
             indent_space: 2,
+            indent_style: IndentStyle::Space,
             max_line_length: 80,
             line_wrap: true,
             experimental_args_breaking_consecutive: false,
+            verify: false,
+            end_of_line: EndOfLine::Auto,
+            edition: Edition::E2023,
         }
     }
 }
 
 impl Config {
-    pub fn from_toml(s: &str) -> Result<Self, String> {
-        toml::from_str(s).map_err(|e| e.message().to_string())
+    /// Reads a configuration file, ignoring its [ConfigFile] overrides.
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        Ok(ConfigFile::from_toml(s)?.merged(&[]))
     }
 
     pub fn default_toml() -> String {
         toml::to_string_pretty(&Self::default()).unwrap()
     }
+
+    /// the names of the settings.
+    pub(crate) fn fields() -> Vec<String> {
+        match toml::Value::try_from(Self::default()) {
+            Ok(toml::Value::Table(table)) => table.into_iter().map(|(key, _)| key).collect(),
+            _ => unreachable!("a config is a table"),
+        }
+    }
+
+    /// The configuration of `input`, with the settings of its pragmas: line comments like
+    /// `// typstfmt: max_line_length=120, line_wrap=false` at the top of it.
+    ///
+    /// The formatting functions apply them already, ignoring invalid ones.
+    pub fn with_pragmas(self, input: &str) -> Result<Config, ConfigError> {
+        Ok(self.with_settings(&pragma::settings(input)?))
+    }
+
+    /// the configuration with `settings`, checked beforehand, replacing its own.
+    pub(crate) fn with_settings(self, settings: &toml::Table) -> Config {
+        let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(self) else {
+            unreachable!("a config is a table")
+        };
+        table.extend(settings.clone());
+        toml::Value::Table(table)
+            .try_into()
+            .expect("settings are checked when parsed")
+    }
+
+    /// checks the settings make sense, returns the setting at fault and why otherwise.
+    pub(crate) fn validate(&self) -> Result<(), (&'static str, String)> {
+        if self.indent_space <= 1 {
+            return Err((
+                "indent_space",
+                format!("indent_space must be at least 2, got {}", self.indent_space),
+            ));
+        }
+        if self.max_line_length == 0 {
+            return Err(("max_line_length", "max_line_length can't be 0".to_string()));
+        }
+        Ok(())
+    }
+
+    /// one level of indentation.
+    pub(crate) fn indent(&self) -> String {
+        match self.indent_style {
+            IndentStyle::Space => " ".repeat(self.indent_space),
+            IndentStyle::Tab => "\t".to_string(),
+        }
+    }
+}
+
+/// A configuration file, it may exclude files and its settings may be overridden
+/// for the files matching some globs:
+///
+/// ```toml
+/// max_line_length = 80
+/// exclude = ["vendor/**"]
+///
+/// [[overrides]]
+/// files = ["slides/**"]
+/// max_line_length = 120
+/// line_wrap = false
+/// ```
+///
+/// The globs are relative to the directory of the configuration file, every matching
+/// override applies, in order.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    settings: toml::Table,
+    exclude: Vec<String>,
+    overrides: Vec<Override>,
+}
+
+#[derive(Debug, Clone)]
+struct Override {
+    files: Vec<String>,
+    settings: toml::Table,
+}
+
+/// Where the value of a setting comes from, see [ConfigResolver::explain].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingSource {
+    /// The default value.
+    Default,
+    /// The global configuration file.
+    Global(PathBuf),
+    /// A configuration file.
+    File(PathBuf),
+    /// An `[[overrides]]` of a configuration file and its globs.
+    Override(PathBuf, Vec<String>),
+    /// [ConfigResolver::set], `--set` in the cli.
+    Set,
+    /// A pragma of the input, see [Config::with_pragmas].
+    Pragma,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingSource::Default => write!(f, "default"),
+            SettingSource::Global(path) => write!(f, "global config {}", path.display()),
+            SettingSource::File(path) => write!(f, "{}", path.display()),
+            SettingSource::Override(path, files) => {
+                write!(f, "override {files:?} of {}", path.display())
+            }
+            SettingSource::Set => write!(f, "command line"),
+            SettingSource::Pragma => write!(f, "pragma"),
+        }
+    }
+}
+
+/// A configuration and where each of its settings comes from, see [ConfigResolver::explain].
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// The sources of the settings which aren't [SettingSource::Default].
+    pub sources: BTreeMap<String, SettingSource>,
+}
+
+impl ResolvedConfig {
+    /// Applies the pragmas of `input`, see [Config::with_pragmas].
+    pub fn with_pragmas(mut self, input: &str) -> Result<Self, ConfigError> {
+        let settings = pragma::settings(input)?;
+        self.config = self.config.with_settings(&settings);
+        for key in settings.keys() {
+            self.sources.insert(key.clone(), SettingSource::Pragma);
+        }
+        Ok(self)
+    }
+
+    /// The settings in the order of [Config], their value and source.
+    #[must_use]
+    pub fn settings(&self) -> Vec<(&'static str, toml::Value, &SettingSource)> {
+        let Ok(toml::Value::Table(mut values)) = toml::Value::try_from(self.config) else {
+            unreachable!("a config is a table")
+        };
+        Config::SETTINGS
+            .iter()
+            .map(|(name, _, _)| {
+                let source = self.sources.get(*name).unwrap_or(&SettingSource::Default);
+                (*name, values.remove(*name).unwrap(), source)
+            })
+            .collect()
+    }
+}
+
+/// a table of settings, knowing where they are in the file.
+type Table = BTreeMap<Spanned<String>, Spanned<toml::Value>>;
+
+impl ConfigFile {
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        Self::parse(s).map_err(|mut err| {
+            err.text = s.to_string();
+            err
+        })
+    }
+
+    fn parse(s: &str) -> Result<Self, ConfigError> {
+        #[derive(Deserialize)]
+        struct Overrides {
+            #[serde(default)]
+            overrides: Vec<Spanned<Table>>,
+        }
+        let syntax_error = |e: toml::de::Error| ConfigError::new(e.message()).at(e.span());
+        let table: Table = toml::from_str(s).map_err(syntax_error)?;
+
+        let mut settings = toml::Table::new();
+        let mut exclude = vec![];
+        for (key, value) in &table {
+            match key.get_ref().as_str() {
+                "exclude" => exclude = globs(key, value)?,
+                "overrides" if !value.get_ref().is_array() => {
+                    return Err(ConfigError::new(
+                        "overrides must be an array of tables: [[overrides]]",
+                    )
+                    .key("overrides")
+                    .at(Some(value.span())));
+                }
+                "overrides" => {}
+                _ => {
+                    check_setting(key, value)?;
+                    settings.insert(key.get_ref().clone(), value.get_ref().clone());
+                }
+            }
+        }
+        let base = Self {
+            settings,
+            exclude,
+            overrides: vec![],
+        };
+        validate(base.merged(&[]), &[&table])?;
+
+        let Overrides { overrides } = toml::from_str(s).map_err(syntax_error)?;
+        let mut file = base.clone();
+        for o in &overrides {
+            let parsed = Override::from_toml(o)?;
+            validate(base.merged(&[&parsed]), &[o.get_ref(), &table])?;
+            file.overrides.push(parsed);
+        }
+        Ok(file)
+    }
+
+    /// The configuration of the file at `path`, relative to the directory of the configuration file.
+    #[must_use]
+    pub fn config_for(&self, path: &Path) -> Config {
+        let overrides = self
+            .overrides
+            .iter()
+            .filter(|o| o.matches(path))
+            .collect::<Vec<_>>();
+        self.merged(&overrides)
+    }
+
+    /// records where the settings of the file at `path` come from, `base` being the source
+    /// of the settings outside of the overrides and `file` the path of this file.
+    pub(crate) fn sources(
+        &self,
+        path: &Path,
+        base: &SettingSource,
+        file: &Path,
+        sources: &mut BTreeMap<String, SettingSource>,
+    ) {
+        for key in self.settings.keys() {
+            sources.insert(key.clone(), base.clone());
+        }
+        for o in self.overrides.iter().filter(|o| o.matches(path)) {
+            for key in o.settings.keys() {
+                let source = SettingSource::Override(file.to_path_buf(), o.files.clone());
+                sources.insert(key.clone(), source);
+            }
+        }
+    }
+
+    /// Tells if the file at `path`, relative to the directory of the configuration file,
+    /// is excluded from formatting when searching a directory.
+    #[must_use]
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        matches_any(&self.exclude, path)
+    }
+
+    /// settings are checked one by one when reading the file, they can be merged safely.
+    fn merged(&self, overrides: &[&Override]) -> Config {
+        let mut settings = self.settings.clone();
+        for o in overrides {
+            settings.extend(o.settings.clone());
+        }
+        toml::Value::Table(settings)
+            .try_into()
+            .expect("settings are checked when reading the file")
+    }
+}
+
+impl Override {
+    fn from_toml(table: &Spanned<Table>) -> Result<Self, ConfigError> {
+        let mut files = None;
+        let mut settings = toml::Table::new();
+        for (key, value) in table.get_ref() {
+            if key.get_ref() == "files" {
+                files = Some(globs(key, value)?);
+            } else {
+                check_setting(key, value)?;
+                settings.insert(key.get_ref().clone(), value.get_ref().clone());
+            }
+        }
+        let files = files.ok_or_else(|| {
+            ConfigError::new("missing field `files` in [[overrides]]")
+                .key("files")
+                .at(Some(table.span()))
+        })?;
+        Ok(Self { files, settings })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        matches_any(&self.files, path)
+    }
+}
+
+/// parses and checks a `key=value` setting, the value being a TOML value or a bare string.
+pub(crate) fn parse_setting(setting: &str) -> Result<toml::Table, ConfigError> {
+    let (key, value) = setting
+        .split_once('=')
+        .ok_or_else(|| ConfigError::new(format!("expected KEY=VALUE, got {setting:?}")))?;
+    let (key, value) = (key.trim(), value.trim());
+    if key == "exclude" || key == "overrides" {
+        return Err(
+            ConfigError::new(format!("`{key}` can only be set in a configuration file")).key(key),
+        );
+    }
+    let mut text = format!("{key} = {value}");
+    if toml::from_str::<toml::Table>(&text).is_err() {
+        text = format!("{key} = {}", toml::Value::String(value.to_string()));
+    }
+    Ok(ConfigFile::from_toml(&text)?.settings)
+}
+
+/// checks a setting is known and has a valid type.
+fn check_setting(key: &Spanned<String>, value: &Spanned<toml::Value>) -> Result<(), ConfigError> {
+    let fields = Config::fields();
+    if !fields.contains(key.get_ref()) {
+        let mut error = ConfigError::new(format!("unknown setting `{}`", key.get_ref()))
+            .key(key.get_ref())
+            .at(Some(key.span()));
+        error.suggestion = utils::closest(key.get_ref(), &fields).map(str::to_string);
+        return Err(error);
+    }
+    let mut table = toml::Table::new();
+    table.insert(key.get_ref().clone(), value.get_ref().clone());
+    toml::Value::Table(table)
+        .try_into::<Config>()
+        .map_err(|e| {
+            ConfigError::new(format!("invalid `{}`: {}", key.get_ref(), e.message()))
+                .key(key.get_ref())
+                .at(Some(value.span()))
+        })?;
+    Ok(())
+}
+
+/// checks the settings make sense together, pointing at the value of the setting at fault
+/// in the first of `tables` defining it.
+fn validate(config: Config, tables: &[&Table]) -> Result<(), ConfigError> {
+    let Err((key, message)) = config.validate() else {
+        return Ok(());
+    };
+    let range = tables
+        .iter()
+        .find_map(|table| table.get_key_value(key))
+        .map(|(_, value)| value.span());
+    Err(ConfigError::new(message).key(key).at(range))
+}
+
+/// reads the array of globs of the `key` setting.
+fn globs(key: &Spanned<String>, value: &Spanned<toml::Value>) -> Result<Vec<String>, ConfigError> {
+    let error =
+        |message: String, range| ConfigError::new(message).key(key.get_ref()).at(Some(range));
+    let Some(values) = value.get_ref().as_array() else {
+        return Err(error(
+            format!("{} must be an array of globs", key.get_ref()),
+            value.span(),
+        ));
+    };
+    values
+        .iter()
+        .map(|glob| {
+            let Some(glob) = glob.as_str() else {
+                return Err(error(
+                    format!("{} must be an array of globs", key.get_ref()),
+                    value.span(),
+                ));
+            };
+            globmatch::Builder::new(glob)
+                .build_glob()
+                .map_err(|e| error(format!("invalid glob {glob:?}: {e}"), value.span()))?;
+            Ok(glob.to_string())
+        })
+        .collect()
+}
+
+fn matches_any(globs: &[String], path: &Path) -> bool {
+    globs.iter().any(|glob| {
+        globmatch::Builder::new(glob)
+            .build_glob()
+            .is_ok_and(|glob| glob.is_match(path))
+    })
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::doc::{Buffer, Doc};
 
 #[derive(Default)]
 pub(crate) struct Ctx {
@@ -8,6 +9,13 @@ pub(crate) struct Ctx {
     pub(crate) off: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CtxState {
+    just_spaced: bool,
+    consec_new_line: i32,
+    off: bool,
+}
+
 /// you may push into your own buffer using this to ensure you push considering context
 ///
 /// you may then push said buffer the final result.
@@ -23,33 +31,33 @@ impl Ctx {
     /// - putting two consecutive spaces.
     /// - putting more than two consecutive newlines.
     /// - trims the string if it DOES contain a newline.
-    /// Won't work for indents.
+    /// Tabs are spaces too, won't work for indents.
     #[instrument(skip_all)]
-    pub(crate) fn push_in(&mut self, s: &str, result: &mut String) {
+    pub(crate) fn push_in(&mut self, s: &str, result: &mut impl Buffer) {
         let s = if s.contains('\n') {
-            s.trim_end_matches(' ')
+            s.trim_end_matches([' ', '\t'])
         } else {
             s
         };
         for c in s.chars() {
             match c {
-                ' ' => {
+                ' ' | '\t' => {
                     if self.just_spaced || result.ends_with(' ') {
                     } else {
                         self.just_spaced = true;
-                        result.push(' ');
+                        result.push_str(" ");
                     }
                 }
                 '\n' => {
                     if self.consec_new_line <= 1 {
                         self.consec_new_line += 1;
-                        result.push('\n');
+                        result.push_str("\n");
                     } else {
                         debug!("IGNORED newline");
                     }
                 }
                 _ => {
-                    result.push(c);
+                    result.push_str(c.encode_utf8(&mut [0; 4]));
                     self.lost_context();
                 }
             }
@@ -58,25 +66,21 @@ impl Ctx {
 
     /// makes the context aware it missed info,
     /// should be called when pushing directly in result.
-    pub(crate) fn push_raw_in(&mut self, s: &str, result: &mut String) {
+    pub(crate) fn push_raw_in(&mut self, s: &str, result: &mut impl Buffer) {
         result.push_str(s);
         self.lost_context();
     }
 
-    /// adds an indentation for each line the input except the first to match the current level of indentation.
-    pub(crate) fn push_raw_indent(&mut self, s: &str, result: &mut String) {
-        for (i, s) in s.split_inclusive('\n').enumerate() {
-            if i == 0 {
-                self.push_raw_in(s, result);
-                continue;
-            }
-            let s = if s.ends_with("\n") {
-                format!("{}\n", s.trim_end())
-            } else {
-                s.to_string()
-            };
-            self.push_raw_in(&format!("{}{}", self.get_indent(), s), result);
-        }
+    /// pushes an already formatted doc.
+    pub(crate) fn push_doc(&mut self, doc: Doc, result: &mut Vec<Doc>) {
+        result.push(doc);
+        self.lost_context();
+    }
+
+    /// pushes an already formatted doc, indenting the lines it starts one more level
+    /// than the current one.
+    pub(crate) fn push_doc_indent(&mut self, doc: Doc, result: &mut Vec<Doc>) {
+        self.push_doc(Doc::indent(doc), result);
     }
 
     // pub(crate) fn push_in_indent(&mut self, s: &str, result: &mut String) {
@@ -103,8 +107,23 @@ impl Ctx {
         self.consec_new_line = 0;
     }
 
-    /// returns an indent using config to get it's length.
+    /// what is remembered between pushes, see [Ctx::restore].
+    pub(crate) fn state(&self) -> CtxState {
+        CtxState {
+            just_spaced: self.just_spaced,
+            consec_new_line: self.consec_new_line,
+            off: self.off,
+        }
+    }
+
+    pub(crate) fn restore(&mut self, state: CtxState) {
+        self.just_spaced = state.just_spaced;
+        self.consec_new_line = state.consec_new_line;
+        self.off = state.off;
+    }
+
+    /// returns an indent using config to get it's length and style.
     pub(crate) fn get_indent(&self) -> String {
-        " ".repeat(self.config.indent_space)
+        self.config.indent()
     }
 }
@@ -0,0 +1,48 @@
+//! Unified diffs between an input and its formatted version.
+use std::fmt::Write;
+
+use similar::{ChangeTag, TextDiff};
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// A unified diff from `original` to `formatted`, empty if they're the same.
+///
+/// Its `--- a/path` and `+++ b/path` headers let `git apply` or `patch -p1` apply it.
+/// With `color`, the headers are bold, removed lines red and added lines green.
+#[must_use]
+pub fn unified_diff(original: &str, formatted: &str, path: &str, color: bool) -> String {
+    let mut res = String::new();
+    if original == formatted {
+        return res;
+    }
+    let paint = |style: &str, text: &str| {
+        if color && !style.is_empty() {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+    let _ = writeln!(res, "{}", paint(BOLD, &format!("--- a/{path}")));
+    let _ = writeln!(res, "{}", paint(BOLD, &format!("+++ b/{path}")));
+    let diff = TextDiff::from_lines(original, formatted);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        let _ = writeln!(res, "{}", paint(CYAN, &hunk.header().to_string()));
+        for change in hunk.iter_changes() {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ('-', RED),
+                ChangeTag::Insert => ('+', GREEN),
+                ChangeTag::Equal => (' ', ""),
+            };
+            let line = change.value().strip_suffix('\n').unwrap_or(change.value());
+            let _ = writeln!(res, "{}", paint(style, &format!("{sign}{line}")));
+            if change.missing_newline() {
+                res.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    res
+}
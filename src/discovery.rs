@@ -0,0 +1,264 @@
+//! Finding the configuration file applying to an input.
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use super::*;
+
+/// The name of configuration files, they may also be hidden: `.typstfmt.toml`.
+pub const CONFIG_FILE_NAME: &str = "typstfmt.toml";
+/// The name of the global configuration, see [confy].
+pub const APP_NAME: &str = "typstfmt";
+/// configuration files aren't searched above a directory containing one of these.
+const ROOT_MARKERS: [&str; 2] = [".git", "typst.toml"];
+
+/// tells if `dir` is the root of a project.
+pub(crate) fn is_root(dir: &Path) -> bool {
+    ROOT_MARKERS.iter().any(|marker| dir.join(marker).exists())
+}
+
+/// Resolves the configuration of each input: the closest configuration file found
+/// walking up from its directory, stopping at the root of the project, or the global
+/// configuration if there is none.
+///
+/// Lookups are cached, each directory is searched and each configuration file is read once.
+///
+/// A configuration file can also be given explicitly, configuration files can be ignored
+/// and settings can be set over them, see [ConfigResolver::set].
+#[derive(Default)]
+pub struct ConfigResolver {
+    /// the configuration file applying to a directory, if any.
+    dirs: HashMap<PathBuf, Option<PathBuf>>,
+    files: HashMap<PathBuf, ConfigFile>,
+    global: Option<Config>,
+    /// the global configuration file, if it exists.
+    global_file: Option<(PathBuf, ConfigFile)>,
+    warnings: Vec<String>,
+    /// the configuration file applying to every input, if given.
+    explicit: Option<PathBuf>,
+    no_config: bool,
+    no_global_config: bool,
+    /// settings applied over the configuration files.
+    settings: toml::Table,
+}
+
+impl ConfigResolver {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the configuration file at `path` for every input instead of searching for one,
+    /// its overrides and excludes are relative to its directory.
+    #[must_use]
+    pub fn with_config_file(mut self, path: &Path) -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        self.explicit = Some(cwd.join(path));
+        self
+    }
+
+    /// Ignores every configuration file, including the global one: the default
+    /// configuration applies.
+    #[must_use]
+    pub fn without_config_files(mut self) -> Self {
+        self.no_config = true;
+        self
+    }
+
+    /// Uses the default configuration rather than the global one for the inputs
+    /// without a configuration file.
+    #[must_use]
+    pub fn without_global_config(mut self) -> Self {
+        self.no_global_config = true;
+        self
+    }
+
+    /// Sets a setting for every input, over their configuration files: `setting` is
+    /// `key=value`, the value being a TOML value or a bare string, e.g. `end_of_line=lf`.
+    ///
+    /// The setting is checked like the ones of a configuration file, later ones win.
+    pub fn set(&mut self, setting: &str) -> Result<(), ConfigError> {
+        self.settings.extend(config::parse_setting(setting)?);
+        Ok(())
+    }
+
+    /// The configuration of the file at `path`, or of the current directory for `None` (stdin).
+    ///
+    /// The overrides of the configuration file matching `path` are applied, then the
+    /// settings given with [ConfigResolver::set].
+    pub fn config_for(&mut self, path: Option<&Path>) -> Result<Config, ConfigError> {
+        let (input, dir) = input_dir(path);
+        let config = match self.applying_config_file(&dir) {
+            Some(file) => self.load(&file)?.config_for(relative_to(&file, &input)),
+            None => self.global()?,
+        };
+        Ok(config.with_settings(&self.settings))
+    }
+
+    /// Like [ConfigResolver::config_for] but also tells where each setting comes from.
+    pub fn explain(&mut self, path: Option<&Path>) -> Result<ResolvedConfig, ConfigError> {
+        let config = self.config_for(path)?;
+        let (input, dir) = input_dir(path);
+        let mut sources = BTreeMap::new();
+        match self.applying_config_file(&dir) {
+            Some(file) => {
+                let source = SettingSource::File(file.clone());
+                let relative = relative_to(&file, &input);
+                self.load(&file)?
+                    .sources(relative, &source, &file, &mut sources);
+            }
+            None => {
+                if let Some((file, global)) = &self.global_file {
+                    let source = SettingSource::Global(file.clone());
+                    global.sources(Path::new(""), &source, file, &mut sources);
+                }
+            }
+        }
+        for key in self.settings.keys() {
+            sources.insert(key.clone(), SettingSource::Set);
+        }
+        Ok(ResolvedConfig { config, sources })
+    }
+
+    /// Tells if the file or directory at `path` is excluded by the configuration file
+    /// applying to it.
+    pub fn is_excluded(&mut self, path: &Path) -> Result<bool, ConfigError> {
+        let (input, dir) = input_dir(Some(path));
+        let Some(file) = self.applying_config_file(&dir) else {
+            return Ok(false);
+        };
+        Ok(self.load(&file)?.is_excluded(relative_to(&file, &input)))
+    }
+
+    /// The configuration file applying to the inputs of `dir`, `None` if the global
+    /// configuration applies.
+    pub fn config_file(&mut self, dir: &Path) -> Option<PathBuf> {
+        if let Some(found) = self.dirs.get(dir) {
+            return found.clone();
+        }
+        let found = self.find_config_file(dir).or_else(|| {
+            if is_root(dir) {
+                return None;
+            }
+            self.config_file(dir.parent()?)
+        });
+        self.dirs.insert(dir.to_path_buf(), found.clone());
+        found
+    }
+
+    /// Takes the warnings about the configuration files found since the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// the configuration file applying to the inputs of `dir`, taking the explicit one and
+    /// `no_config` into account.
+    fn applying_config_file(&mut self, dir: &Path) -> Option<PathBuf> {
+        if self.no_config {
+            return None;
+        }
+        if self.explicit.is_some() {
+            return self.explicit.clone();
+        }
+        self.config_file(dir)
+    }
+
+    /// the configuration file in `dir`, `typstfmt.toml` is preferred to `.typstfmt.toml`.
+    fn find_config_file(&mut self, dir: &Path) -> Option<PathBuf> {
+        let file = dir.join(CONFIG_FILE_NAME);
+        let hidden_file = dir.join(format!(".{CONFIG_FILE_NAME}"));
+        match (file.is_file(), hidden_file.is_file()) {
+            (true, true) => {
+                self.warnings.push(format!(
+                    "Both {file:?} and {hidden_file:?} are present. Using {file:?}."
+                ));
+                Some(file)
+            }
+            (true, false) => Some(file),
+            (false, true) => Some(hidden_file),
+            (false, false) => None,
+        }
+    }
+
+    fn load(&mut self, file: &Path) -> Result<&ConfigFile, ConfigError> {
+        if !self.files.contains_key(file) {
+            let config = self.read(file)?;
+            self.files.insert(file.to_path_buf(), config);
+        }
+        Ok(&self.files[file])
+    }
+
+    fn read(&self, file: &Path) -> Result<ConfigFile, ConfigError> {
+        let content = std::fs::read_to_string(file).map_err(|err| {
+            ConfigError::new(format!("failed to read config file: {err}"))
+                .in_file(file.to_path_buf(), "")
+        })?;
+        ConfigFile::from_toml(&content).map_err(|err| err.in_file(file.to_path_buf(), &content))
+    }
+
+    fn global(&mut self) -> Result<Config, ConfigError> {
+        if let Some(config) = self.global {
+            return Ok(config);
+        }
+        if self.no_config || self.no_global_config {
+            return Ok(Config::default());
+        }
+        let config_path = confy::get_configuration_file_path(APP_NAME, APP_NAME).map_err(|e| {
+            ConfigError::new(format!("error loading global configuration file: {e}"))
+        })?;
+        // confy creates it if it doesn't exist yet.
+        let config = if config_path.is_file() {
+            let file = self.read(&config_path)?;
+            let config = file.config_for(Path::new(""));
+            self.global_file = Some((config_path, file));
+            config
+        } else {
+            confy::load(APP_NAME, APP_NAME).map_err(|e| {
+                ConfigError::new(format!("error loading global configuration file: {e}"))
+                    .in_file(config_path, "")
+            })?
+        };
+        self.global = Some(config);
+        Ok(config)
+    }
+}
+
+/// `path` relative to the current directory made absolute, without `.` and `..` so that its
+/// parents are its ancestors, symlinks aren't resolved.
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            _ => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// an input and the directory it is in, absolute so that all its parents can be searched.
+///
+/// stdin is the current directory.
+fn input_dir(path: Option<&Path>) -> (PathBuf, PathBuf) {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    match path {
+        Some(path) => {
+            let input = absolute(path);
+            let dir = input
+                .parent()
+                .map_or_else(|| cwd.clone(), Path::to_path_buf);
+            (input, dir)
+        }
+        None => (cwd.clone(), cwd),
+    }
+}
+
+/// the path of `input` relative to the directory of the configuration `file`.
+fn relative_to<'a>(file: &Path, input: &'a Path) -> &'a Path {
+    file.parent()
+        .and_then(|config_dir| input.strip_prefix(config_dir).ok())
+        .unwrap_or(input)
+}
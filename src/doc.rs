@@ -0,0 +1,408 @@
+//! The document formatting functions produce and the printer turning it into text.
+//!
+//! Formatting functions don't decide where lines break, they describe where they may
+//! break with [Doc::Group]s, [Doc::Line]s and [Doc::Fill]s, in the manner of Wadler's
+//! "A prettier printer". The printer then knows the column each group starts at and
+//! what follows it on it's line when deciding to break it or not.
+
+use super::*;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Doc {
+    #[default]
+    Nil,
+    /// text, newlines in it are followed by the current indentation.
+    Text(String),
+    /// a space, or a newline if the group it's in breaks.
+    Line,
+    /// nothing, or a newline if the group it's in breaks.
+    SoftLine,
+    /// a newline, breaking every group it's in.
+    HardLine,
+    /// indents the lines started inside.
+    Indent(Box<Doc>),
+    /// indents the lines started inside if the group it's in breaks.
+    IndentIfBreak(Box<Doc>),
+    /// printed flat if it fits on the line, broken otherwise.
+    Group(Box<Doc>),
+    /// the first doc if the group it's in breaks, the second one otherwise.
+    IfBreak(Box<Doc>, Box<Doc>),
+    /// contents and the separators between them, only the separators followed
+    /// by a content that doesn't fit on the line break.
+    Fill(Vec<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub(crate) fn text(s: impl AsRef<str>) -> Self {
+        Doc::Text(s.as_ref().to_string())
+    }
+
+    pub(crate) fn concat(docs: Vec<Doc>) -> Self {
+        Doc::Concat(docs)
+    }
+
+    pub(crate) fn indent(doc: Doc) -> Self {
+        Doc::Indent(Box::new(doc))
+    }
+
+    pub(crate) fn indent_if_break(doc: Doc) -> Self {
+        Doc::IndentIfBreak(Box::new(doc))
+    }
+
+    pub(crate) fn group(doc: Doc) -> Self {
+        Doc::Group(Box::new(doc))
+    }
+
+    pub(crate) fn if_break(broken: Doc, flat: Doc) -> Self {
+        Doc::IfBreak(Box::new(broken), Box::new(flat))
+    }
+
+    /// prints the doc on it's own, see [print].
+    pub(crate) fn render(&self, config: &Config) -> String {
+        print(self, config, "", 0)
+    }
+
+    /// tells if the doc contains a newline even when printed flat.
+    pub(crate) fn has_newline(&self) -> bool {
+        match self {
+            Doc::Text(s) => s.contains('\n'),
+            Doc::HardLine => true,
+            Doc::Nil | Doc::Line | Doc::SoftLine => false,
+            Doc::Indent(doc) | Doc::IndentIfBreak(doc) | Doc::Group(doc) => doc.has_newline(),
+            Doc::IfBreak(_, flat) => flat.has_newline(),
+            Doc::Fill(docs) | Doc::Concat(docs) => docs.iter().any(Doc::has_newline),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Doc::Nil => true,
+            Doc::Text(s) => s.is_empty(),
+            Doc::Line | Doc::SoftLine | Doc::HardLine => false,
+            Doc::Indent(doc) | Doc::IndentIfBreak(doc) | Doc::Group(doc) => doc.is_empty(),
+            Doc::IfBreak(broken, flat) => broken.is_empty() && flat.is_empty(),
+            Doc::Fill(docs) | Doc::Concat(docs) => docs.iter().all(Doc::is_empty),
+        }
+    }
+
+    /// the last char printed, `None` if the doc is empty or if it depends on
+    /// how the groups break.
+    fn last_char(&self) -> Option<char> {
+        match self {
+            Doc::Nil | Doc::Line | Doc::SoftLine => None,
+            Doc::Text(s) => s.chars().next_back(),
+            Doc::HardLine => Some('\n'),
+            // a newline at the end is followed by the indentation.
+            Doc::Indent(doc) | Doc::IndentIfBreak(doc) => match doc.last_char() {
+                Some('\n') => Some(' '),
+                c => c,
+            },
+            Doc::Group(doc) => doc.last_char(),
+            Doc::IfBreak(broken, flat) => {
+                Some(broken.last_char()?).filter(|&c| Some(c) == flat.last_char())
+            }
+            Doc::Fill(docs) | Doc::Concat(docs) => last_char(docs),
+        }
+    }
+
+    /// removes the spaces and newlines at the end, returns true if nothing is left.
+    fn trim_end(&mut self) -> bool {
+        match self {
+            Doc::Nil | Doc::Line | Doc::SoftLine | Doc::HardLine => true,
+            Doc::Text(s) => {
+                s.truncate(s.trim_end_matches([' ', '\n']).len());
+                s.is_empty()
+            }
+            Doc::Indent(doc) | Doc::IndentIfBreak(doc) | Doc::Group(doc) => doc.trim_end(),
+            Doc::IfBreak(broken, flat) => {
+                let broken = broken.trim_end();
+                flat.trim_end() && broken
+            }
+            Doc::Fill(docs) | Doc::Concat(docs) => {
+                trim_end(docs);
+                docs.is_empty()
+            }
+        }
+    }
+}
+
+fn last_char(docs: &[Doc]) -> Option<char> {
+    docs.iter().rev().find(|d| !d.is_empty())?.last_char()
+}
+
+/// removes the spaces and newlines at the end of a sequence of docs.
+pub(crate) fn trim_end(docs: &mut Vec<Doc>) {
+    while let Some(last) = docs.last_mut() {
+        if !last.trim_end() {
+            break;
+        }
+        docs.pop();
+    }
+}
+
+/// something text can be pushed in, see [Ctx::push_in].
+pub(crate) trait Buffer {
+    fn push_str(&mut self, s: &str);
+    fn ends_with(&self, c: char) -> bool;
+}
+
+impl Buffer for String {
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s);
+    }
+
+    fn ends_with(&self, c: char) -> bool {
+        str::ends_with(self, c)
+    }
+}
+
+impl Buffer for Vec<Doc> {
+    fn push_str(&mut self, s: &str) {
+        match self.last_mut() {
+            Some(Doc::Text(text)) => text.push_str(s),
+            _ => self.push(Doc::text(s)),
+        }
+    }
+
+    fn ends_with(&self, c: char) -> bool {
+        last_char(self) == Some(c)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// what's left to print, with the indentation level and the mode it's printed in.
+#[derive(Clone, Copy)]
+enum Cmd<'a> {
+    Doc(usize, Mode, &'a Doc),
+    /// the rest of a [Doc::Fill], starting with a content.
+    Fill(usize, Mode, &'a [Doc]),
+    /// the rest of a [Doc::Fill], starting with a separator.
+    Separator(usize, Mode, &'a [Doc]),
+}
+
+struct Printer<'a> {
+    width: usize,
+    indent: &'a str,
+    unit: String,
+    tab_width: usize,
+}
+
+/// Prints the doc starting at `column` of a line indented by `indent`,
+/// the lines started inside are indented by `indent` too.
+pub(crate) fn print(doc: &Doc, config: &Config, indent: &str, column: usize) -> String {
+    let printer = Printer {
+        width: config.max_line_length,
+        indent,
+        unit: config.indent(),
+        tab_width: config.indent_space,
+    };
+    printer.print(doc, column)
+}
+
+impl<'a> Printer<'a> {
+    fn width(&self, s: &str) -> usize {
+        utils::str_width(s, self.tab_width)
+    }
+
+    fn indent_width(&self, level: usize) -> usize {
+        self.width(self.indent) + level * self.width(&self.unit)
+    }
+
+    fn print(&self, doc: &Doc, column: usize) -> String {
+        let mut out = Output {
+            printer: self,
+            text: String::new(),
+            col: column,
+            pending_indent: None,
+        };
+        let mut cmds = vec![Cmd::Doc(0, Mode::Break, doc)];
+        while let Some(cmd) = cmds.pop() {
+            let (level, mode, doc) = match cmd {
+                Cmd::Doc(level, mode, doc) => (level, mode, doc),
+                Cmd::Fill(level, mode, parts) => {
+                    if let Some((content, rest)) = parts.split_first() {
+                        if !rest.is_empty() {
+                            cmds.push(Cmd::Separator(level, mode, rest));
+                        }
+                        cmds.push(Cmd::Doc(level, mode, content));
+                    }
+                    continue;
+                }
+                Cmd::Separator(level, mode, parts) => {
+                    let (separator, rest) = parts.split_first().unwrap();
+                    let mut next = vec![];
+                    if let Some(content) = rest.first() {
+                        next.push(Cmd::Doc(level, Mode::Flat, content));
+                    }
+                    next.push(Cmd::Doc(level, Mode::Flat, separator));
+                    // the last content is followed by what comes after the fill on its line.
+                    let after: &[Cmd] = if rest.len() <= 1 { &cmds } else { &[] };
+                    let separator_mode = if self.fits(next, after, out.col, true) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    cmds.push(Cmd::Fill(level, mode, rest));
+                    cmds.push(Cmd::Doc(level, separator_mode, separator));
+                    continue;
+                }
+            };
+
+            match doc {
+                Doc::Nil => {}
+                Doc::Text(s) => {
+                    for (i, line) in s.split('\n').enumerate() {
+                        if i != 0 {
+                            out.newline(level);
+                        }
+                        out.push(line);
+                    }
+                }
+                Doc::Line if mode == Mode::Flat => out.push(" "),
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine | Doc::HardLine => out.newline(level),
+                Doc::Indent(doc) => cmds.push(Cmd::Doc(level + 1, mode, doc)),
+                Doc::IndentIfBreak(doc) => {
+                    let level = if mode == Mode::Break {
+                        level + 1
+                    } else {
+                        level
+                    };
+                    cmds.push(Cmd::Doc(level, mode, doc));
+                }
+                Doc::Group(doc) => {
+                    let mode = if mode == Mode::Flat
+                        || self.fits(
+                            vec![Cmd::Doc(level, Mode::Flat, doc)],
+                            &cmds,
+                            out.col,
+                            false,
+                        ) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    cmds.push(Cmd::Doc(level, mode, doc));
+                }
+                Doc::IfBreak(broken, flat) => {
+                    let doc = if mode == Mode::Break { broken } else { flat };
+                    cmds.push(Cmd::Doc(level, mode, doc));
+                }
+                Doc::Fill(parts) => cmds.push(Cmd::Fill(level, mode, parts)),
+                Doc::Concat(docs) => {
+                    cmds.extend(docs.iter().rev().map(|doc| Cmd::Doc(level, mode, doc)));
+                }
+            }
+        }
+        out.text
+    }
+
+    /// tells if `next` fits on the line, followed by what's left of the line in `rest`.
+    ///
+    /// Every line of the texts in `next` must fit, unless `first_line_only`.
+    fn fits(&self, next: Vec<Cmd>, rest: &[Cmd], col: usize, first_line_only: bool) -> bool {
+        let mut remaining = self.width as isize - col as isize;
+        let mut stack = next.into_iter().map(|cmd| (cmd, false)).collect_vec();
+        let mut rest = rest.iter().rev();
+        loop {
+            if remaining < 0 {
+                return false;
+            }
+            let Some((cmd, in_rest)) = stack.pop().or_else(|| Some((*rest.next()?, true))) else {
+                return true;
+            };
+            let (level, mode, doc) = match cmd {
+                Cmd::Doc(level, mode, doc) => (level, mode, doc),
+                Cmd::Fill(level, mode, parts) | Cmd::Separator(level, mode, parts) => {
+                    stack.extend(
+                        parts
+                            .iter()
+                            .rev()
+                            .map(|doc| (Cmd::Doc(level, mode, doc), in_rest)),
+                    );
+                    continue;
+                }
+            };
+            match doc {
+                Doc::Nil => {}
+                Doc::Text(s) => {
+                    let mut lines = s.split('\n');
+                    remaining -= self.width(lines.next().unwrap_or_default()) as isize;
+                    for line in lines {
+                        if remaining < 0 {
+                            return false;
+                        }
+                        if in_rest || first_line_only {
+                            return true;
+                        }
+                        remaining = self.width as isize
+                            - self.indent_width(level) as isize
+                            - self.width(line) as isize;
+                    }
+                }
+                Doc::Line if mode == Mode::Flat => remaining -= 1,
+                Doc::SoftLine if mode == Mode::Flat => {}
+                Doc::Line | Doc::SoftLine => return true,
+                Doc::HardLine => return in_rest || first_line_only,
+                Doc::Indent(doc) => stack.push((Cmd::Doc(level + 1, mode, doc), in_rest)),
+                Doc::IndentIfBreak(doc) => {
+                    let level = if mode == Mode::Break {
+                        level + 1
+                    } else {
+                        level
+                    };
+                    stack.push((Cmd::Doc(level, mode, doc), in_rest));
+                }
+                Doc::Group(doc) => stack.push((Cmd::Doc(level, mode, doc), in_rest)),
+                Doc::IfBreak(broken, flat) => {
+                    let doc = if mode == Mode::Break { broken } else { flat };
+                    stack.push((Cmd::Doc(level, mode, doc), in_rest));
+                }
+                Doc::Fill(docs) | Doc::Concat(docs) => {
+                    stack.extend(
+                        docs.iter()
+                            .rev()
+                            .map(|doc| (Cmd::Doc(level, mode, doc), in_rest)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// the text printed so far.
+struct Output<'a> {
+    printer: &'a Printer<'a>,
+    text: String,
+    col: usize,
+    /// the indentation is only pushed before some text, lines are never left with trailing spaces.
+    pending_indent: Option<usize>,
+}
+
+impl Output<'_> {
+    fn push(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        if let Some(level) = self.pending_indent.take() {
+            self.text.push_str(self.printer.indent);
+            for _ in 0..level {
+                self.text.push_str(&self.printer.unit);
+            }
+        }
+        self.text.push_str(s);
+        self.col += self.printer.width(s);
+    }
+
+    fn newline(&mut self, level: usize) {
+        self.text.push('\n');
+        self.col = self.printer.indent_width(level);
+        self.pending_indent = Some(level);
+    }
+}
@@ -0,0 +1,98 @@
+use std::ops::Range;
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use typst_syntax::SyntaxNode;
+
+use super::*;
+
+/// Replaces the `range` (in bytes) of the original text with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Formats the input and returns the edits turning it into the formatted output.
+///
+/// See [text_edits].
+#[must_use]
+pub fn format_edits(s: &str, config: Config) -> Vec<TextEdit> {
+    text_edits(s, &format(s, config))
+}
+
+/// Returns the smallest set of edits turning `original` into `formatted`.
+///
+/// Both are diffed token by token, the edits are sorted and don't overlap,
+/// their ranges refer to `original`.
+#[must_use]
+pub fn text_edits(original: &str, formatted: &str) -> Vec<TextEdit> {
+    let old = tokens(original);
+    let new = tokens(formatted);
+
+    let mut edits: Vec<TextEdit> = vec![];
+    let mut old_offset = 0;
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        let (old_range, new_range) = (op.old_range(), op.new_range());
+        let old_len: usize = old[old_range].iter().map(|t| t.len()).sum();
+        if let DiffOp::Equal { .. } = op {
+            old_offset += old_len;
+            continue;
+        }
+        let replacement: String = new[new_range].concat();
+        let range = old_offset..old_offset + old_len;
+        old_offset += old_len;
+        match edits.last_mut() {
+            // consecutive deletions and insertions are one edit.
+            Some(last) if last.range.end == range.start => {
+                last.range.end = range.end;
+                last.replacement.push_str(&replacement);
+            }
+            _ => edits.push(TextEdit { range, replacement }),
+        }
+    }
+
+    edits
+        .into_iter()
+        .filter_map(|edit| shrink(original, edit))
+        .collect()
+}
+
+/// the text of the leaves of the syntax tree, in order.
+fn tokens(s: &str) -> Vec<&str> {
+    fn push_leaves<'a>(node: &SyntaxNode, s: &'a str, offset: &mut usize, res: &mut Vec<&'a str>) {
+        if node.children().len() == 0 {
+            let len = node.len();
+            res.push(&s[*offset..*offset + len]);
+            *offset += len;
+        }
+        for child in node.children() {
+            push_leaves(child, s, offset, res);
+        }
+    }
+    let mut res = vec![];
+    push_leaves(&parse(s), s, &mut 0, &mut res);
+    res
+}
+
+/// removes the common start and end of the replaced text and its replacement.
+fn shrink(original: &str, edit: TextEdit) -> Option<TextEdit> {
+    let old = &original[edit.range.clone()];
+    let new = edit.replacement.as_str();
+    let prefix = common_len(old.chars(), new.chars());
+    let suffix = common_len(old[prefix..].chars().rev(), new[prefix..].chars().rev());
+    if old.len() == prefix + suffix && new.len() == prefix + suffix {
+        return None;
+    }
+    Some(TextEdit {
+        range: edit.range.start + prefix..edit.range.end - suffix,
+        replacement: new[prefix..new.len() - suffix].to_string(),
+    })
+}
+
+/// length in bytes of the chars both iterators start with.
+fn common_len(old: impl Iterator<Item = char>, new: impl Iterator<Item = char>) -> usize {
+    old.zip(new)
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::*;
+
+/// A syntax error found in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte range of the erroneous node in the input.
+    pub range: Range<usize>,
+    /// 1-based line of the start of the range.
+    pub line: usize,
+    /// 1-based column (in chars) of the start of the range.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The input couldn't be parsed, formatting was refused.
+    Parse(Vec<ParseError>),
+    /// The formatted output doesn't parse like the input, see [Config::verify].
+    Verification(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Parse(errors) => {
+                write!(f, "could not parse input ({} error(s))", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+            FormatError::Verification(diagnostic) => {
+                write!(
+                    f,
+                    "formatting would change the meaning of the input, {diagnostic}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// An invalid configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The configuration file, if the configuration was read from one.
+    pub path: Option<PathBuf>,
+    /// The content of the configuration file, [ConfigError::range] refers to it.
+    pub text: String,
+    pub message: String,
+    /// The setting at fault.
+    pub key: Option<String>,
+    /// A known setting close to an unknown [ConfigError::key].
+    pub suggestion: Option<String>,
+    /// byte range of the erroneous key or value in [ConfigError::text].
+    pub range: Option<Range<usize>>,
+}
+
+impl ConfigError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            text: String::new(),
+            message: message.into(),
+            key: None,
+            suggestion: None,
+            range: None,
+        }
+    }
+
+    pub(crate) fn at(mut self, range: Option<Range<usize>>) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub(crate) fn key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    /// sets the file the error was found in.
+    pub(crate) fn in_file(mut self, path: PathBuf, text: &str) -> Self {
+        self.path = Some(path);
+        self.text = text.to_string();
+        self
+    }
+
+    /// 1-based line and column (in chars) of the start of [ConfigError::range].
+    #[must_use]
+    pub fn line_column(&self) -> Option<(usize, usize)> {
+        let range = self.range.as_ref()?;
+        (range.start <= self.text.len()).then(|| utils::line_column(&self.text, range.start))
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+            if let Some((line, column)) = self.line_column() {
+                write!(f, "{line}:{column}:")?;
+            }
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// An error while searching a directory for typst files.
+#[derive(Debug)]
+pub enum WalkError {
+    /// A directory or an ignore file couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The configuration of a file or directory is invalid.
+    Config(ConfigError),
+}
+
+impl From<ConfigError> for WalkError {
+    fn from(error: ConfigError) -> Self {
+        WalkError::Config(error)
+    }
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkError::Io(path, error) => write!(f, "couldn't read {}: {error}", path.display()),
+            WalkError::Config(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for WalkError {}
+
+/// An error while asking git for the changed lines of a file.
+#[derive(Debug)]
+pub enum GitError {
+    /// git couldn't be run.
+    Io(io::Error),
+    /// git failed, holds what it printed.
+    Failed(String),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::Io(error) => write!(f, "couldn't run git: {error}"),
+            GitError::Failed(message) => write!(f, "git failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GitError {}
+
+/// collects every `Error` node of the tree in the order they appear in the input.
+pub(crate) fn collect_errors(node: &LinkedNode, s: &str, errors: &mut Vec<ParseError>) {
+    if !node.erroneous() {
+        return;
+    }
+    if node.kind() == Error {
+        let range = node.range();
+        let (line, column) = utils::line_column(s, range.start);
+        let message = node
+            .errors()
+            .first()
+            .map(|e| e.message.to_string())
+            .unwrap_or_else(|| "syntax error".to_string());
+        errors.push(ParseError {
+            line,
+            column,
+            range,
+            message,
+        });
+        return;
+    }
+    for child in node.children() {
+        collect_errors(&child, s, errors);
+    }
+}
+
+/// A node is broken if one of it's direct children is an error,
+/// we don't try to format those and leave them as is.
+///
+/// Nodes that are sequences of unrelated items (Markup, Code, Math) are never broken,
+/// their healthy children still get formatted and the error itself is kept verbatim.
+pub(crate) fn is_broken(node: &LinkedNode) -> bool {
+    node.erroneous()
+        && ![Markup, Code, Math].contains(&node.kind())
+        && node.children().any(|c| c.kind() == Error)
+}
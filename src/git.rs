@@ -0,0 +1,145 @@
+//! The lines of a file changed according to git, to only format them.
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process::Command;
+
+use crate::GitError;
+
+/// What the lines of a file are compared to, to find the ones changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changes {
+    /// The working tree against a revision, e.g. `main` or `HEAD~3`.
+    Since(String),
+    /// The changes staged in the index against `HEAD`.
+    Staged,
+}
+
+/// a hunk of a diff: its first line and number of lines on each side, like in
+/// `@@ -old_start,old_len +new_start,new_len @@`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+}
+
+impl Changes {
+    /// The lines of the file at `path` in the working tree which changed, 1-based.
+    ///
+    /// With [Changes::Since], an untracked file changed entirely, ignored or not since it was
+    /// asked for.
+    ///
+    /// With [Changes::Staged], only the staged hunks changed. They are mapped through the unstaged
+    /// changes to the lines of the working tree.
+    pub fn lines(&self, path: &Path) -> Result<Vec<RangeInclusive<usize>>, GitError> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let diff = |args: &[&str]| -> Result<Vec<Hunk>, GitError> {
+            let mut all = vec!["diff", "--no-color", "--no-ext-diff", "-U0"];
+            all.extend(args);
+            all.extend(["--", &name]);
+            Ok(hunks(&git(dir, &all)?))
+        };
+        let changed = match self {
+            Changes::Since(revision) => {
+                let untracked = git(dir, &["ls-files", "--others", "--", &name])?;
+                if !untracked.trim().is_empty() {
+                    return Ok(vec![1..=usize::MAX]);
+                }
+                diff(&[revision])?
+                    .iter()
+                    .filter_map(Hunk::new_lines)
+                    .collect()
+            }
+            Changes::Staged => {
+                let unstaged = diff(&[])?;
+                diff(&["--cached"])?
+                    .iter()
+                    .filter_map(Hunk::new_lines)
+                    .map(|lines| {
+                        let start = *map_line(&unstaged, *lines.start()).start();
+                        let end = *map_line(&unstaged, *lines.end()).end();
+                        start..=end.max(start)
+                    })
+                    .collect()
+            }
+        };
+        Ok(changed)
+    }
+}
+
+impl Hunk {
+    /// the lines of the new side, `None` for a deletion.
+    fn new_lines(&self) -> Option<RangeInclusive<usize>> {
+        (self.new_len > 0).then(|| self.new_start..=self.new_start + self.new_len - 1)
+    }
+}
+
+/// runs git in `dir`, returns what it printed.
+fn git(dir: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(GitError::Io)?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(GitError::Failed(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// the hunks of a diff of a single file.
+fn hunks(diff: &str) -> Vec<Hunk> {
+    // `start,len` or `start` for a single line.
+    let side = |s: &str| -> Option<(usize, usize)> {
+        match s.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+    diff.lines()
+        .filter_map(|line| {
+            let mut ranges = line.strip_prefix("@@ -")?.split(' ');
+            let (old_start, old_len) = side(ranges.next()?)?;
+            let (new_start, new_len) = side(ranges.next()?.strip_prefix('+')?)?;
+            Some(Hunk {
+                old_start,
+                old_len,
+                new_start,
+                new_len,
+            })
+        })
+        .collect()
+}
+
+/// where the line `line` of the old side of a diff is on its new side, the lines replacing it
+/// if it changed.
+fn map_line(hunks: &[Hunk], line: usize) -> RangeInclusive<usize> {
+    let mut shifted = line;
+    for hunk in hunks {
+        // a hunk without old lines inserts after its start.
+        let old_start = if hunk.old_len == 0 {
+            hunk.old_start + 1
+        } else {
+            hunk.old_start
+        };
+        if line < old_start {
+            break;
+        }
+        if line < old_start + hunk.old_len {
+            let new_start = hunk.new_start.max(1);
+            return new_start
+                ..=(hunk.new_start + hunk.new_len)
+                    .saturating_sub(1)
+                    .max(new_start);
+        }
+        shifted = shifted + hunk.new_len - hunk.old_len;
+    }
+    shifted..=shifted
+}
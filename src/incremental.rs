@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use typst_syntax::{Source, SyntaxNode};
+
+use super::*;
+use crate::context::CtxState;
+
+/// Keeps a parsed document and the formatted output of its top level nodes
+/// so that it can be formatted again quickly after small edits.
+///
+/// Only the top level nodes that changed since the last [IncrementalFormatter::format]
+/// are formatted again, the result is the same as calling [format] on the whole text.
+pub struct IncrementalFormatter {
+    source: Source,
+    config: Config,
+    /// the configuration the cache was made with, pragmas may change it.
+    cached_config: Config,
+    cache: HashMap<CacheKey, Cached>,
+}
+
+/// a top level node and the context it was formatted in.
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    node: SyntaxNode,
+    before: CtxState,
+}
+
+struct Cached {
+    formatted: Doc,
+    after: CtxState,
+}
+
+impl IncrementalFormatter {
+    #[must_use]
+    pub fn new(text: &str, config: Config) -> Self {
+        Self {
+            source: Source::detached(text),
+            config,
+            cached_config: config,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The current text of the document.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.source.text()
+    }
+
+    /// Replaces `range` (in bytes) of the document with `with`, only reparsing what changed.
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn edit(&mut self, range: Range<usize>, with: &str) {
+        self.source.edit(range, with);
+    }
+
+    /// Replaces the whole document, only reparsing what changed.
+    pub fn replace(&mut self, text: &str) {
+        self.source.replace(text);
+    }
+
+    /// Formats the current document.
+    pub fn format(&mut self) -> String {
+        let text = self.source.text();
+        if text.contains('\r') {
+            // line endings are replaced before parsing, our tree doesn't match.
+            self.cache.clear();
+            return format(text, self.config);
+        }
+
+        let config = pragma::apply(text, self.config);
+        if config != self.cached_config {
+            self.cache.clear();
+            self.cached_config = config;
+        }
+        let root = LinkedNode::new(self.source.root());
+        let mut ctx = Ctx::from_config(config);
+        let mut cache = HashMap::with_capacity(self.cache.len());
+        let mut children = vec![];
+        for child in root.children() {
+            let key = CacheKey {
+                node: child.get().clone(),
+                before: ctx.state(),
+            };
+            let cached = match self.cache.remove(&key) {
+                Some(cached) => cached,
+                None => {
+                    let formatted = visit(&child, &mut ctx);
+                    Cached {
+                        formatted,
+                        after: ctx.state(),
+                    }
+                }
+            };
+            ctx.restore(cached.after);
+            children.push(cached.formatted.clone());
+            cache.insert(key, cached);
+        }
+        self.cache = cache;
+
+        let doc = format_node(&root, children, &mut ctx);
+        let formatted = utils::trim_trailing_spaces(&doc::print(&doc, &config, "", 0));
+        let formatted = utils::with_line_endings(&formatted, config.end_of_line.resolve(text));
+        verify::checked(text, formatted, config)
+    }
+}
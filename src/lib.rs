@@ -5,6 +5,8 @@
     clippy::print_stderr,
     clippy::dbg_macro
 )]
+// config errors are rare and carry what's needed to print them with the lines at fault.
+#![allow(clippy::result_large_err)]
 
 use itertools::Itertools;
 use tracing::debug;
@@ -16,71 +18,166 @@ use typst_syntax::SyntaxKind::*;
 use typst_syntax::{parse, LinkedNode};
 use Option::None;
 
+mod schema;
+
+pub use schema::config_schema;
+
 mod config;
 
-pub use config::Config;
+pub use config::{
+    Config, ConfigFile, Edition, EndOfLine, IndentStyle, ResolvedConfig, SettingSource,
+};
+
+mod discovery;
+
+pub use discovery::{ConfigResolver, APP_NAME, CONFIG_FILE_NAME};
+
+mod walk;
+
+pub use walk::{is_skipped, typst_files};
 
 mod context;
 
 use context::Ctx;
 
+mod doc;
+
+use doc::{Buffer as _, Doc};
+
+mod error;
+
+pub use error::{ConfigError, FormatError, GitError, ParseError, WalkError};
+
 mod utils;
 
+mod pragma;
+use pragma::Pragma;
+
+mod range;
+
+pub use range::{
+    format_line_ranges, format_lines, format_range, format_ranges, try_format_line_ranges,
+    try_format_ranges,
+};
+
+mod edits;
+
+pub use edits::{format_edits, text_edits, TextEdit};
+
+mod git;
+
+pub use git::Changes;
+
+mod diff;
+
+pub use diff::unified_diff;
+
+mod report;
+
+pub use report::{changed_lines, FileReport, ReportFormat};
+
+mod verify;
+
+mod incremental;
+
+pub use incremental::IncrementalFormatter;
+
+pub mod lsp;
+
 mod binary;
 mod code_blocks;
 mod markup;
 mod math;
 mod params;
 
+/// Formats the input, broken regions are left verbatim.
+///
+/// With [Config::verify], the input is returned unchanged if formatting it would change it's meaning.
 #[must_use]
 pub fn format(s: &str, config: Config) -> String {
-    //replace tabs
-    let s = &s.replace('\t', &" ".repeat(config.indent_space));
+    let config = pragma::apply(s, config);
+    verify::checked(s, format_unverified(s, config), config)
+}
+
+fn format_unverified(s: &str, config: Config) -> String {
+    let eol = config.end_of_line.resolve(s);
+    let s = &utils::normalize_line_endings(s);
 
     let init = parse(s);
     let mut context = Ctx::from_config(config);
     let root = LinkedNode::new(&init);
-    let s = visit(&root, &mut context);
-    regex::Regex::new("( )+\n")
-        .unwrap()
-        .replace_all(&s, "\n")
-        .to_string()
+    let doc = visit(&root, &mut context);
+    let formatted = utils::trim_trailing_spaces(&doc::print(&doc, &config, "", 0));
+    utils::with_line_endings(&formatted, eol)
+}
+
+/// Like [format] but refuses to format an input containing syntax errors,
+/// returning where they are instead.
+///
+/// [format] itself leaves the broken regions of such an input verbatim and formats the rest.
+///
+/// With [Config::verify], returns what changed if formatting would change the meaning of the input.
+pub fn try_format(s: &str, config: Config) -> Result<String, FormatError> {
+    let config = pragma::apply(s, config);
+    let root = parse(s);
+    let mut errors = vec![];
+    error::collect_errors(&LinkedNode::new(&root), s, &mut errors);
+    if !errors.is_empty() {
+        return Err(FormatError::Parse(errors));
+    }
+    let formatted = format_unverified(s, config);
+    if config.verify {
+        if let Some(diagnostic) = verify::difference(s, &formatted) {
+            return Err(FormatError::Verification(diagnostic));
+        }
+    }
+    Ok(formatted)
 }
 
 /// This is recursively called on the AST, the formatting is bottom up,
-/// nodes will decide based on the size of their children and the max line length
-/// how they will be formatted.
+/// nodes describe with a [Doc] how they may break, the printer then decides
+/// based on the max line length how they will be formatted.
 ///
 /// One assumed rule is that no kind should be formatting with surrounded space
 #[instrument(skip_all, name = "V", fields(kind = format!("{:?}",node.kind())))]
-fn visit(node: &LinkedNode, ctx: &mut Ctx) -> String {
-    let mut res: Vec<String> = vec![];
+fn visit(node: &LinkedNode, ctx: &mut Ctx) -> Doc {
+    let mut res: Vec<Doc> = vec![];
     for child in node.children() {
         let child_fmt = visit(&child, ctx);
         res.push(child_fmt);
     }
+    format_node(node, res, ctx)
+}
+
+/// Formats a node once its children have been visited.
+fn format_node(node: &LinkedNode, res: Vec<Doc>, ctx: &mut Ctx) -> Doc {
     let res = match node.kind() {
-        LineComment => format_comment_handling_disable(node, &res, ctx),
-        _ if ctx.off => no_format(node, &res, ctx),
-        Binary => binary::format_bin_left_assoc(node, &res, ctx),
-        Named | Keyed => format_named_args(node, &res, ctx),
-        ListItem | EnumItem | TermItem => format_list_enum(node, &res, ctx),
-        CodeBlock => code_blocks::format_code_blocks(node, &res, ctx),
-        Markup => markup::format_markup(node, &res, ctx),
-        ContentBlock => markup::format_content_blocks(node, &res, ctx),
+        LineComment => Doc::text(format_comment_handling_disable(node, ctx)),
+        _ if ctx.off => no_format(node, res, ctx),
+        _ if error::is_broken(node) => {
+            ctx.lost_context();
+            Doc::text(deep_no_format(node))
+        }
+        Binary => binary::format_bin_left_assoc(node, res, ctx),
+        Named | Keyed => format_named_args(node, res, ctx),
+        ListItem | EnumItem | TermItem => format_list_enum(node, res, ctx),
+        CodeBlock => code_blocks::format_code_blocks(node, res, ctx),
+        Markup => markup::format_markup(node, res, ctx),
+        ContentBlock => markup::format_content_blocks(node, res, ctx),
         Args | Params | Dict | Array | Destructuring | Parenthesized => {
-            params::format_args(node, &res, ctx)
+            params::format_args(node, res, ctx)
         }
-        LetBinding => format_let_binding(node, &res, ctx),
-        Conditional => conditional_format(node, &res, ctx),
+        LetBinding => format_let_binding(node, res, ctx),
+        Closure if ctx.config.edition >= Edition::E2024 => format_closure(node, res, ctx),
+        Conditional => conditional_format(node, res, ctx),
         Raw | BlockComment => {
             ctx.lost_context();
-            node.text().to_string()
+            Doc::text(node.text())
         }
-        Equation => math::format_equation(node, &res, ctx),
-        Math => math::format_math(node, &res, ctx),
-        Str => no_format(node, &res, ctx),
-        _ => format_default(node, &res, ctx),
+        Equation => math::format_equation(node, res, ctx),
+        Math => math::format_math(node, res, ctx),
+        Str => no_format(node, res, ctx),
+        _ => format_default(node, res, ctx),
     };
     if node.children().count() == 0 {
         debug!("TOKEN : {:?}", node.kind());
@@ -99,132 +196,147 @@ fn visit(node: &LinkedNode, ctx: &mut Ctx) -> String {
 ///
 /// For the already formatted children, change nothing.
 #[instrument(skip_all, ret)]
-fn format_default(node: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
+fn format_default(node: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
     ctx.push_in(node.text(), &mut res);
     for s in children {
-        ctx.push_raw_in(s, &mut res);
+        ctx.push_doc(s, &mut res);
     }
-    res
+    Doc::concat(res)
 }
 
-fn no_format(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
+fn no_format(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
     ctx.push_raw_in(parent.text(), &mut res);
     for s in children {
-        ctx.push_raw_in(s, &mut res);
+        ctx.push_doc(s, &mut res);
     }
-    res
+    Doc::concat(res)
 }
 
 fn deep_no_format(parent: &LinkedNode) -> String {
-    let mut res: Vec<String> = vec![];
+    let mut res = parent.text().to_string();
     for child in parent.children() {
-        let child_fmt = deep_no_format(&child);
-        res.push(child_fmt);
+        res.push_str(&deep_no_format(&child));
     }
-    no_format(parent, &res, &mut Ctx::default())
+    res
 }
 
-fn conditional_format(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
+fn conditional_format(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
     ctx.push_raw_in(parent.text(), &mut res);
-    for (s, node) in children.iter().zip(parent.children()) {
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             Space => {}
             If => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 ctx.push_raw_in(" ", &mut res);
             }
             CodeBlock | ContentBlock => {
                 ctx.push_raw_in(" ", &mut res);
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
             Else => {
                 ctx.push_raw_in(" ", &mut res);
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 if node.next_sibling_kind() == Some(Conditional) {
                     ctx.push_raw_in(" ", &mut res);
                 }
             }
-            _ => ctx.push_raw_in(s, &mut res),
+            _ => ctx.push_doc(s, &mut res),
         }
     }
-    res
+    Doc::concat(res)
 }
 
 #[instrument(skip_all, ret)]
-pub(crate) fn format_named_args(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+pub(crate) fn format_named_args(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             Show | Set => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 ctx.push_in(" ", &mut res);
             }
             Colon => res.push_str(": "),
             Space => {}
-            LineComment | BlockComment => ctx.push_raw_in(s, &mut res),
+            LineComment | BlockComment => ctx.push_doc(s, &mut res),
             _ => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
         }
     }
-    res
+    Doc::concat(res)
 }
 
 #[instrument(skip_all, ret)]
-pub(crate) fn format_let_binding(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+pub(crate) fn format_let_binding(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             Eq => {
                 ctx.push_in(" ", &mut res);
-                ctx.push_in(s, &mut res);
+                ctx.push_in(&s.render(&ctx.config), &mut res);
                 ctx.push_in(" ", &mut res);
             }
-            Space => ctx.push_in(s, &mut res),
+            Space => ctx.push_in(&s.render(&ctx.config), &mut res),
             _ => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
         }
     }
-    res
+    Doc::concat(res)
+}
+
+/// spaces the `=` of `let f(x) = x`.
+#[instrument(skip_all, ret)]
+fn format_closure(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            Eq => {
+                ctx.push_in(" ", &mut res);
+                ctx.push_in(&s.render(&ctx.config), &mut res);
+                ctx.push_in(" ", &mut res);
+            }
+            Space => ctx.push_in(&s.render(&ctx.config), &mut res),
+            _ => ctx.push_doc(s, &mut res),
+        }
+    }
+    Doc::concat(res)
 }
 
-fn format_comment_handling_disable(parent: &LinkedNode, _: &[String], ctx: &mut Ctx) -> String {
+fn format_comment_handling_disable(parent: &LinkedNode, ctx: &mut Ctx) -> String {
     ctx.lost_context();
-    if parent.text().contains("typstfmt::off") {
-        ctx.off = true;
-    } else if parent.text().contains("typstfmt::on") {
-        ctx.off = false;
-    } else if parent.text().contains("typstfmt::") {
-        warn!("your comment contains `typstfmt::` not followed by `on` or `off`, did you make a typo?");
+    match Pragma::parse(parent.text()) {
+        Some(Pragma::Off) => ctx.off = true,
+        Some(Pragma::On) => ctx.off = false,
+        _ if parent.text().contains("typstfmt::") => {
+            warn!("your comment contains `typstfmt::` not followed by `on` or `off`, did you make a typo?");
+        }
+        _ => {}
     }
     parent.text().to_string()
 }
 
-fn format_list_enum(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
+fn format_list_enum(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             EnumMarker | ListMarker | TermMarker => {
                 ctx.push_raw_in(node.text(), &mut res);
             }
             _ => {
-                ctx.push_raw_indent(s, &mut res);
+                ctx.push_doc_indent(s, &mut res);
             }
         }
     }
-    res
+    Doc::concat(res)
 }
 
 #[cfg(test)]
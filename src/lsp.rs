@@ -0,0 +1,277 @@
+//! A minimal language server, speaking LSP over any reader and writer (stdio for the cli).
+//!
+//! Supports full document synchronization and `textDocument/formatting`,
+//! `textDocument/rangeFormatting` and `textDocument/onTypeFormatting`.
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use super::*;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves requests read from `input` until the client sends `exit` or closes `input`.
+///
+/// Malformed messages are answered with an error and the server keeps going, only reading
+/// `input` or writing `output` failing stops it.
+///
+/// `config_for` gives the configuration to use for a document from its path,
+/// `None` if the document isn't a file.
+pub fn serve(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    config_for: impl Fn(Option<&std::path::Path>) -> Config,
+) -> io::Result<()> {
+    let mut documents: HashMap<String, String> = HashMap::new();
+    while let Some(message) = read_message(&mut input)? {
+        let message = match message {
+            Ok(message) if message.is_object() => message,
+            Ok(_) => {
+                let error = (INVALID_REQUEST, "a message must be an object".to_string());
+                write_message(&mut output, &reply(&Value::Null, Err(error)))?;
+                continue;
+            }
+            Err(message) => {
+                write_message(
+                    &mut output,
+                    &reply(&Value::Null, Err((PARSE_ERROR, message))),
+                )?;
+                continue;
+            }
+        };
+        let Some(method) = message.get("method") else {
+            // the response of the client to a request, the server doesn't send any.
+            debug!("ignored response {message}");
+            continue;
+        };
+        let method = method.as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            // notification
+            match method {
+                "exit" => return Ok(()),
+                "textDocument/didOpen" => {
+                    if let (Some(uri), Some(text)) = (
+                        params["textDocument"]["uri"].as_str(),
+                        params["textDocument"]["text"].as_str(),
+                    ) {
+                        documents.insert(uri.to_string(), text.to_string());
+                    }
+                }
+                "textDocument/didChange" => {
+                    let uri = params["textDocument"]["uri"].as_str();
+                    // full synchronization, the last change is the whole document.
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    if let (Some(uri), Some(text)) = (uri, text) {
+                        documents.insert(uri.to_string(), text.to_string());
+                    }
+                }
+                "textDocument/didClose" => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        documents.remove(uri);
+                    }
+                }
+                _ => debug!("ignored notification {method}"),
+            }
+            continue;
+        };
+
+        let response = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "documentFormattingProvider": true,
+                    "documentRangeFormattingProvider": true,
+                    "documentOnTypeFormattingProvider": {
+                        "firstTriggerCharacter": "\n",
+                        "moreTriggerCharacter": ["}", "]", ")"],
+                    },
+                },
+                "serverInfo": { "name": "typstfmt" },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/formatting"
+            | "textDocument/rangeFormatting"
+            | "textDocument/onTypeFormatting" => {
+                handle_formatting(method, params, &documents, &config_for)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method {method}"))),
+        };
+
+        write_message(&mut output, &reply(id, response))?;
+    }
+    Ok(())
+}
+
+/// the response to the request `id`, `null` if it couldn't be read.
+fn reply(id: &Value, response: Result<Value, (i64, String)>) -> Value {
+    match response {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+}
+
+fn handle_formatting(
+    method: &str,
+    params: &Value,
+    documents: &HashMap<String, String>,
+    config_for: &impl Fn(Option<&std::path::Path>) -> Config,
+) -> Result<Value, (i64, String)> {
+    let uri = params["textDocument"]["uri"]
+        .as_str()
+        .ok_or((INVALID_PARAMS, "missing textDocument.uri".to_string()))?;
+    let text = documents
+        .get(uri)
+        .ok_or((INVALID_PARAMS, format!("unknown document {uri}")))?;
+    let config = config_for(uri_to_path(uri).as_deref());
+
+    let formatted = match method {
+        "textDocument/rangeFormatting" => {
+            let range = lsp_range(text, &params["range"])
+                .ok_or((INVALID_PARAMS, "invalid range".to_string()))?;
+            format_range(text, range, config)
+        }
+        "textDocument/onTypeFormatting" => {
+            let offset = offset(text, &params["position"])
+                .ok_or((INVALID_PARAMS, "invalid position".to_string()))?;
+            // after a newline, the line that was just finished gets formatted.
+            let (line, _) = utils::line_column(text, offset);
+            let line = if params["ch"] == "\n" {
+                line.saturating_sub(1).max(1)
+            } else {
+                line
+            };
+            format_lines(text, line..=line, config)
+        }
+        _ => format(text, config),
+    };
+
+    let edits = text_edits(text, &formatted)
+        .into_iter()
+        .map(|edit| {
+            json!({
+                "range": {
+                    "start": position(text, edit.range.start),
+                    "end": position(text, edit.range.end),
+                },
+                "newText": edit.replacement,
+            })
+        })
+        .collect_vec();
+    Ok(Value::Array(edits))
+}
+
+/// reads a message framed by a `Content-Length` header, `None` at the end of the input.
+///
+/// a message without a valid header or which isn't JSON is an error saying why.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Value, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Ok(Some(Err("missing Content-Length header".to_string())));
+    };
+    let mut content = vec![0; length];
+    match input.read_exact(&mut content) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    Ok(Some(
+        serde_json::from_slice(&content).map_err(|err| format!("invalid JSON: {err}")),
+    ))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
+
+/// the path of a `file://` uri.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        match (b, tail) {
+            (b'%', [h, l, tail @ ..]) => {
+                let hex = [*h, *l];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                rest = tail;
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // windows paths look like `file:///C:/...`
+    let path = match path.strip_prefix('/') {
+        Some(p) if p.get(1..2) == Some(":") => p.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// byte offset of a LSP position, its character being counted in UTF-16 code units.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = if line == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line_text.char_indices() {
+        if units >= character {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line_text.len())
+}
+
+fn lsp_range(text: &str, range: &Value) -> Option<Range<usize>> {
+    let start = offset(text, &range["start"])?;
+    let end = offset(text, &range["end"])?;
+    Some(start..end.max(start))
+}
+
+/// LSP position of a byte offset.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
@@ -2,39 +2,129 @@
 #![warn(clippy::dbg_macro)]
 
 use std::{
-    ffi::OsString,
+    cell::RefCell,
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fmt,
     fs::File,
-    io::{stdin, stdout, Read, Write},
+    io::{self, stdin, stdout, IsTerminal, Read, Write},
+    ops::RangeInclusive,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
 };
 
 use lexopt::prelude::*;
-use typstfmt::{format, Config};
+use typstfmt::{
+    config_schema, is_skipped, try_format, try_format_line_ranges, typst_files, unified_diff,
+    Changes, Config, ConfigError, ConfigResolver, FileReport, FormatError, GitError, ReportFormat,
+    WalkError, APP_NAME, CONFIG_FILE_NAME,
+};
 
 const VERSION: &str = env!("TYPSTFMT_VERSION");
-// `DOT_CONFIG_FILE_NAME` is not created as a const due to the fact that we
-// would have to duplicate the whole string slice, because
-// `format!(".{CONFIG_FILE_NAME}")` (non-const function) cannot be applied to
-// `const` (or `static`) values in Rust (1.72.1).
-const CONFIG_FILE_NAME: &str = "typstfmt.toml";
-/// Note: used in [`confy`](https://crates.io/crates/confy) functions.
-const APP_NAME: &str = "typstfmt";
+/// Exit code when running with `--check`, `--diff` or `--report-format` and an input
+/// needs formatting.
+const EXIT_NEEDS_FORMATTING: i32 = 1;
+/// Exit code when an input couldn't be parsed, it is left untouched.
+const EXIT_PARSE_ERROR: i32 = 2;
+/// Exit code when running with `--verify` and formatting an input would change it's meaning,
+/// it is left untouched.
+const EXIT_VERIFY_FAILED: i32 = 3;
+/// Exit code when an input or a directory couldn't be read, an output written or git couldn't
+/// tell the changed lines of an input, the other inputs are still formatted.
+const EXIT_IO_ERROR: i32 = 4;
+/// Exit code when a configuration file is invalid, nothing is formatted, or when the pragmas
+/// of an input are invalid, it is left untouched.
+const EXIT_CONFIG_ERROR: i32 = 5;
+/// Exit code when the command line is invalid, nothing is formatted.
+const EXIT_USAGE_ERROR: i32 = 6;
 const HELP: &str = r#"Format Typst code
 
-usage: typstfmt [options] [file...]
+usage: typstfmt [options] [file|directory...]
+       typstfmt lsp
+       typstfmt config schema
+       typstfmt config show [--config PATH] [--no-config] [--no-global-config] [--set KEY=VALUE] [file]
 
 If no file is specified, stdin will be used.
 Files will be overwritten unless --output is passed.
 
+Directories are searched for *.typ files, skipping the ones ignored by a .gitignore
+or a .typstfmtignore and the ones matching the `exclude` globs of their typstfmt.toml.
+
+Each file uses the closest typstfmt.toml or .typstfmt.toml in its directory or
+its parents, up to the root of the project (a directory containing .git or typst.toml),
+stdin uses the one of the current directory. The global configuration is used if
+there is none.
+
+A document can change its settings with comments at its top, before anything else:
+// typstfmt: max_line_length=120, line_wrap=false
+
+Subcommands:
+        lsp                         Run a language server over stdio, providing formatting,
+                                    range formatting and on type formatting.
+        config schema               Print the JSON Schema of typstfmt.toml, for completion and
+                                    validation in editors.
+        config show [file]          Print the configuration of a file, or of stdin, and where
+                                    each setting comes from: default, global config, config file,
+                                    override, command line or pragma.
+
 Options:
         -o, --output                If not specified, files will be overwritten. '-' for stdout.
+        --stdin-filepath PATH       The path of the file read from stdin, which isn't touched:
+                                    its configuration is found from it, it is echoed untouched if
+                                    it is ignored or excluded and errors are reported on it.
+        --backup[=SUFFIX]           Before overwriting a file, copy it to its path followed by
+                                    SUFFIX, `~` by default.
         --stdout                    Same as `--output -` (Deprecated, here for compatibility).
         --check                     Run in 'check' mode. Exits with 0 if input is
                                     formatted correctly. Exits with 1 if formatting is required.
+        --diff                      Print a unified diff of the inputs needing formatting, which
+                                    `git apply` or `patch -p1` can apply, and leave them untouched.
+                                    Exits with 1 if there is a diff.
+        --color WHEN                Color the diff: auto (the default, if stdout is a terminal
+                                    and NO_COLOR isn't set), always or never.
+        --report-format FORMAT      Like --check but print a report of the lines needing
+                                    formatting once every input is checked: json, sarif,
+                                    checkstyle or github (annotations of GitHub Actions).
+        --lines START:END           Only format the code within these lines, the first line being 1.
+                                    Errors outside of those lines are tolerated, the ones within
+                                    them and --verify failures are reported as for a whole file.
+        --changed-since REV         Only format the code of the lines changed since the git
+                                    revision REV, untracked files entirely, even ignored ones
+                                    given explicitly, the rest is left untouched. Errors are
+                                    reported as with --lines.
+        --staged                    Like --changed-since but for the lines staged in git.
+        --verify                    Check that formatting doesn't change the meaning of the input,
+                                    leave it untouched otherwise.
+        -j, --jobs N                Format N files at once, defaults to the number of CPUs.
+                                    The output is in the order of the inputs whatever N.
         --verbose                   increase verbosity for non errors
         -v, --version               Prints the current version.
         -h, --help                  Prints this help.
+        --config PATH               Use this configuration file for every input instead of
+                                    searching for one.
+        --no-config                 Ignore configuration files, including the global one.
+        --no-global-config          Use the default configuration rather than the global one
+                                    for the inputs without a configuration file.
+        --set KEY=VALUE             Set a setting over the configuration files, e.g.
+                                    `--set max_line_length=120`, may be repeated.
         --get-global-config-path    Prints the path of the global configuration file.
         -C, --make-default-config   Create a default config file at typstfmt.toml
+
+Exit codes:
+        0   Success.
+        1   With --check, --diff or --report-format, some input needs formatting.
+        2   Some input couldn't be parsed, it was left untouched.
+        3   With --verify, formatting some input would change it's meaning, it was left untouched.
+        4   Some input or directory couldn't be read, some output written or git couldn't tell
+            the changed lines of some input, the other inputs were still formatted.
+        5   A configuration file, a --set setting or a pragma is invalid, with an invalid
+            pragma the input is left untouched.
+        6   The command line is invalid, nothing was formatted.
+        When several of these happen, the highest code is used.
 "#;
 
 enum Inputs {
@@ -44,67 +134,194 @@ enum Inputs {
 
 struct Input {
     name: String,
+    /// `None` for stdin.
+    path: Option<PathBuf>,
     content: String,
 }
 
 impl Inputs {
-    fn read(&self) -> Box<dyn Iterator<Item = Input> + '_> {
+    /// the path of each input, `None` for stdin.
+    fn paths(self) -> Vec<Option<OsString>> {
         match self {
-            Inputs::Stdin => {
-                let mut input_buf = String::new();
-                stdin()
-                    .read_to_string(&mut input_buf)
-                    .expect("Couldn't read stdin.");
-                Box::new(std::iter::once(Input {
-                    name: "stdin".to_owned(),
-                    content: input_buf,
-                }))
-            }
-            Inputs::Files(paths) => Box::new(paths.iter().map(|path| {
-                let mut input_buf = String::new();
-                let mut file = File::options()
-                    .read(true)
-                    .open(path)
-                    .unwrap_or_else(|err| panic!("Failed to open file {path:?}: {err}"));
-                file.read_to_string(&mut input_buf)
-                    .unwrap_or_else(|err| panic!("Couldn't read file {path:?}: {err}"));
-                Input {
-                    name: path.to_string_lossy().into_owned(),
-                    content: input_buf,
-                }
-            })),
+            Inputs::Stdin => vec![None],
+            Inputs::Files(paths) => paths.into_iter().map(Some).collect(),
+        }
+    }
+}
+
+impl Input {
+    /// reads the file at `path`, or stdin for `None`.
+    fn read(path: Option<&OsString>) -> Result<Input, IoError> {
+        let mut input_buf = String::new();
+        let Some(path) = path else {
+            stdin()
+                .read_to_string(&mut input_buf)
+                .map_err(|err| IoError::new("read", "stdin", err))?;
+            return Ok(Input {
+                name: "stdin".to_owned(),
+                path: None,
+                content: input_buf,
+            });
+        };
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut input_buf))
+            .map_err(|err| IoError::new("read", path, err))?;
+        Ok(Input {
+            name: path.to_string_lossy().into_owned(),
+            path: Some(PathBuf::from(path)),
+            content: input_buf,
+        })
+    }
+}
+
+/// a file couldn't be read or written, or a directory searched.
+struct IoError {
+    /// what couldn't be done: read, write...
+    action: &'static str,
+    /// the file or directory, `stdin` or `stdout`.
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl IoError {
+    fn new(action: &'static str, path: impl Into<PathBuf>, error: io::Error) -> Self {
+        Self {
+            action,
+            path: path.into(),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (action, path) = (self.action, self.path.display());
+        if self.error.kind() == io::ErrorKind::InvalidData && action == "read" {
+            return write!(f, "couldn't read {path}: it isn't valid UTF-8");
+        }
+        write!(f, "couldn't {action} {path}: {}", self.error)
+    }
+}
+
+/// what formatting an input gave.
+enum Outcome {
+    Formatted(String),
+    /// the pragmas of the input are invalid, it is left untouched.
+    InvalidPragma(ConfigError),
+    Failed(FormatError),
+    /// git couldn't tell the changed lines of the input, it is left untouched.
+    Git(GitError),
+}
+
+/// formats `input`, only the `lines` if given.
+fn format_input(
+    input: &Input,
+    config: Config,
+    verify: bool,
+    lines: Option<&[RangeInclusive<usize>]>,
+) -> Outcome {
+    // the formatting functions ignore invalid pragmas, they're reported here.
+    let mut config = match config.with_pragmas(&input.content) {
+        Ok(config) => config,
+        Err(mut err) => {
+            err.path = Some(
+                input
+                    .path
+                    .clone()
+                    .unwrap_or_else(|| input.name.clone().into()),
+            );
+            return Outcome::InvalidPragma(err);
         }
+    };
+    config.verify |= verify;
+    let formatted = match lines {
+        Some(lines) => try_format_line_ranges(&input.content, lines, config),
+        None => try_format(&input.content, config),
+    };
+    match formatted {
+        Ok(formatted) => Outcome::Formatted(formatted),
+        Err(err) => Outcome::Failed(err),
+    }
+}
+
+/// maps `f` over `items` on `jobs` threads, `done` is given the results in the order of
+/// `items`, each as soon as it and the ones before it are ready.
+fn map_in_order<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+    mut done: impl FnMut(R),
+) {
+    if jobs <= 1 || items.len() <= 1 {
+        items.iter().for_each(|item| done(f(item)));
+        return;
     }
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let (sender, next, f) = (sender.clone(), &next, &f);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                if sender.send((index, f(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        // results arrive in any order, they're held until the ones before them are done.
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&expected) {
+                done(result);
+                expected += 1;
+            }
+        }
+    });
 }
 
 enum Output {
-    None,
+    /// overwrites the inputs, first copying them to their path followed by the suffix if any.
+    None(Option<OsString>),
     Check,
+    /// prints the diff of the inputs needing formatting, colored or not.
+    Diff(bool),
+    /// reports the lines needing formatting once every input is checked.
+    Report(ReportFormat),
     Stdout,
     File(OsString),
 }
 
+/// what became of a formatted input.
+enum Written {
+    Done,
+    /// in the modes leaving the inputs untouched, the input isn't formatted.
+    NeedsFormatting,
+}
+
 impl Output {
-    fn write(&self, input: &Input, formatted: &str, verbose: bool) -> Result<(), ()> {
+    fn write(&self, input: &Input, formatted: &str, verbose: bool) -> Result<Written, IoError> {
+        let to_stdout = |s: &str| {
+            (stdout().write_all(s.as_bytes()))
+                .map_err(|err| IoError::new("write to", "stdout", err))
+        };
         match self {
-            Output::None => {
+            Output::None(backup) => {
                 // this is not stdout by the check after parsing the arguments that sets the output
                 // to stdout rather than none for stdin.
-                let path = &input.name;
+                let path = input.path.as_deref().unwrap_or(Path::new(&input.name));
                 if formatted == input.content {
-                    println!("file: {path:?} up to date.");
-                    return Ok(());
+                    println!("file: {:?} up to date.", input.name);
+                    return Ok(Written::Done);
                 }
-                let mut file = File::options()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(path)
-                    .unwrap_or_else(|err| panic!("Couldn't open file: {path:?}: {err}"));
-                file.write_all(formatted.as_bytes())
-                    .unwrap_or_else(|err| panic!("Failed to write to file {path:?}: {err}"));
+                write_atomically(path, formatted, backup.as_deref())?;
                 if verbose {
-                    println!("file: {path:?} overwritten.");
+                    println!("file: {:?} overwritten.", input.name);
                 };
             }
             Output::Check => {
@@ -112,7 +329,23 @@ impl Output {
                     if verbose {
                         println!("{} needs formatting.", input.name);
                     }
-                    return Err(());
+                    return Ok(Written::NeedsFormatting);
+                }
+                if verbose {
+                    println!("{} is already formatted.", input.name);
+                }
+            }
+            Output::Report(_) => {
+                if input.content != formatted {
+                    return Ok(Written::NeedsFormatting);
+                }
+            }
+            Output::Diff(color) => {
+                let path = diff_path(input);
+                let diff = unified_diff(&input.content, formatted, &path, *color);
+                if !diff.is_empty() {
+                    to_stdout(&diff)?;
+                    return Ok(Written::NeedsFormatting);
                 }
                 if verbose {
                     println!("{} is already formatted.", input.name);
@@ -122,33 +355,374 @@ impl Output {
                 if verbose {
                     println!("=== {:?} ===", input.name);
                 };
-                stdout()
-                    .write_all(formatted.as_bytes())
-                    .unwrap_or_else(|err| {
-                        panic!("Couldn't write to stdout: {}", err);
-                    });
+                to_stdout(formatted)?;
             }
             Output::File(output) => {
-                let mut file = File::options()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(output.to_string_lossy().into_owned())
-                    .unwrap_or_else(|err| panic!("Couldn't create output file: {output:?}: {err}"));
-
-                file.write_all(formatted.as_bytes())
-                    .unwrap_or_else(|err| panic!("Couldn't write to file: {output:?}: {err}"));
+                std::fs::write(output, formatted)
+                    .map_err(|err| IoError::new("write", output, err))?;
             }
         }
+        Ok(Written::Done)
+    }
+}
+
+/// replaces the content of the file at `path` without ever leaving it half written: `content` is
+/// written to a temporary file next to it, with the same permissions, which is renamed over it.
+///
+/// with `backup`, the file is first copied to its path followed by this suffix.
+fn write_atomically(path: &Path, content: &str, backup: Option<&OsStr>) -> Result<(), IoError> {
+    let error = |action| move |err| IoError::new(action, path, err);
+    // a symlink is replaced by a file when renaming over it, its target is written instead.
+    let target = std::fs::canonicalize(path).map_err(error("write"))?;
+    let permissions = std::fs::metadata(&target)
+        .map_err(error("write"))?
+        .permissions();
+    if permissions.readonly() {
+        let err = io::Error::new(io::ErrorKind::PermissionDenied, "the file is read-only");
+        return Err(error("write")(err));
+    }
+    if let Some(suffix) = backup {
+        let mut backup = target.clone().into_os_string();
+        backup.push(suffix);
+        std::fs::copy(&target, backup).map_err(error("back up"))?;
+    }
+    let mut temp_name = OsString::from(".");
+    temp_name.push(target.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.typstfmt.tmp", std::process::id()));
+    let temp = target.with_file_name(temp_name);
+    let write = || {
+        let mut file = File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        file.set_permissions(permissions)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp, &target)
+    };
+    write().map_err(|err| {
+        let _ = std::fs::remove_file(&temp);
+        error("write")(err)
+    })
+}
+
+/// the path of `input` in the headers of its diff, without `./` so that `git apply` finds it.
+fn diff_path(input: &Input) -> String {
+    match &input.path {
+        Some(path) => relative_path(path),
+        None => input.name.clone(),
+    }
+}
+
+/// `path` without `./` and with `/` separators.
+fn relative_path(path: &Path) -> String {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// whether to color the output, `--color WHEN`.
+#[derive(Clone, Copy)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("expected auto, always or never, got {s:?}")),
+        }
+    }
+
+    /// `auto` colors if stdout is a terminal and `NO_COLOR` isn't set.
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// the flags changing how the configuration of the inputs is resolved.
+#[derive(Default)]
+struct ConfigOptions {
+    file: Option<PathBuf>,
+    no_config: bool,
+    no_global_config: bool,
+    /// `--set` settings, in order.
+    settings: Vec<String>,
+}
+
+impl ConfigOptions {
+    const FLAGS: [&'static str; 4] = ["config", "no-config", "no-global-config", "set"];
+
+    /// reads the flag `--name`, one of [ConfigOptions::FLAGS], and its value.
+    fn parse(&mut self, name: &str, parser: &mut lexopt::Parser) -> Result<(), lexopt::Error> {
+        match name {
+            "config" => self.file = Some(parser.value()?.into()),
+            "no-config" => self.no_config = true,
+            "no-global-config" => self.no_global_config = true,
+            "set" => self.settings.push(parser.value()?.string()?),
+            _ => unreachable!("unknown config flag --{name}"),
+        }
         Ok(())
     }
+
+    /// the resolver following these options, exits if they're invalid.
+    fn resolver(self) -> ConfigResolver {
+        let mut resolver = ConfigResolver::new();
+        match (self.file, self.no_config) {
+            (Some(_), true) => {
+                eprintln!("error: --config and --no-config can't be used together");
+                std::process::exit(EXIT_CONFIG_ERROR);
+            }
+            (Some(path), false) => resolver = resolver.with_config_file(&path),
+            (None, true) => resolver = resolver.without_config_files(),
+            (None, false) => {}
+        }
+        if self.no_global_config {
+            resolver = resolver.without_global_config();
+        }
+        for setting in self.settings {
+            resolver.set(&setting).unwrap_or_else(|mut err| {
+                err.message = format!("{} in `--set {setting}`", err.message);
+                config_error(&err)
+            });
+        }
+        resolver
+    }
 }
 
-fn main() -> Result<(), lexopt::Error> {
+/// the configuration of an input, printing the warnings about configuration files.
+///
+/// `on_error` gives the configuration to use if it is invalid.
+fn load_config(
+    resolver: &mut ConfigResolver,
+    path: Option<&Path>,
+    on_error: impl FnOnce(&ConfigError) -> Config,
+) -> Config {
+    let config = resolver.config_for(path);
+    for warning in resolver.take_warnings() {
+        eprintln!("Warning! {warning}");
+    }
+    config.unwrap_or_else(|err| on_error(&err))
+}
+
+/// prints the error and the line of the configuration file it is on, then exits.
+fn config_error(err: &ConfigError) -> ! {
+    print_config_error(err);
+    std::process::exit(EXIT_CONFIG_ERROR);
+}
+
+/// prints the error and the line of the configuration file it is on:
+///
+/// ```text
+/// error: unknown setting `max_line_lenght`
+///  --> typstfmt.toml:1:1
+///   |
+/// 1 | max_line_lenght = 80
+///   | ^^^^^^^^^^^^^^^ did you mean `max_line_length`?
+/// ```
+fn print_config_error(err: &ConfigError) {
+    eprintln!("error: {}", err.message);
+    let suggestion = err
+        .suggestion
+        .as_ref()
+        .map(|s| format!(" did you mean `{s}`?"))
+        .unwrap_or_default();
+    let Some(path) = &err.path else {
+        if !suggestion.is_empty() {
+            eprintln!("help:{suggestion}");
+        }
+        return;
+    };
+    let (Some(range), Some((line, column))) = (&err.range, err.line_column()) else {
+        eprintln!(" --> {}", path.display());
+        if !suggestion.is_empty() {
+            eprintln!("help:{suggestion}");
+        }
+        return;
+    };
+    eprintln!(" --> {}:{line}:{column}", path.display());
+    let line_text = err.text.lines().nth(line - 1).unwrap_or_default();
+    let underlined = err.text[range.clone()]
+        .chars()
+        .take_while(|&c| c != '\n')
+        .count()
+        .max(1);
+    let gutter = " ".repeat(line.to_string().len());
+    eprintln!("{gutter} |");
+    eprintln!("{line} | {line_text}");
+    eprintln!(
+        "{gutter} | {}{}{suggestion}",
+        " ".repeat(column - 1),
+        "^".repeat(underlined)
+    );
+}
+
+/// replaces the directories by the typst files they contain.
+///
+/// a directory which couldn't be searched is reported and sets `exit_status`.
+fn expand_dirs(
+    paths: Vec<OsString>,
+    resolver: &mut ConfigResolver,
+    exit_status: &mut i32,
+) -> Vec<OsString> {
+    let mut res = vec![];
+    for path in paths {
+        if Path::new(&path).is_dir() {
+            let files = match typst_files(Path::new(&path), resolver) {
+                Ok(files) => files,
+                Err(WalkError::Config(err)) => config_error(&err),
+                Err(err @ WalkError::Io(..)) => {
+                    eprintln!("{err}");
+                    *exit_status = EXIT_IO_ERROR;
+                    continue;
+                }
+            };
+            res.extend(files.into_iter().map(PathBuf::into_os_string));
+        } else {
+            res.push(path);
+        }
+    }
+    res
+}
+
+/// runs `typstfmt config <command>`.
+fn config_command(mut args: impl Iterator<Item = OsString>) -> Result<(), lexopt::Error> {
+    match args.next() {
+        Some(command) if command == "schema" => {
+            let schema = serde_json::to_string_pretty(&config_schema()).unwrap();
+            println!("{schema}");
+        }
+        Some(command) if command == "show" => {
+            let mut parser = lexopt::Parser::from_args(args);
+            let mut options = ConfigOptions::default();
+            let mut file = None;
+            while let Some(arg) = parser.next()? {
+                match arg {
+                    Long(name) if ConfigOptions::FLAGS.contains(&name) => {
+                        let name = name.to_string();
+                        options.parse(&name, &mut parser)?;
+                    }
+                    Value(path) if file.is_none() => file = Some(PathBuf::from(path)),
+                    _ => return Err(arg.unexpected()),
+                }
+            }
+            show_config(options.resolver(), file.as_deref());
+        }
+        Some(command) => {
+            return Err(
+                format!("unknown config command {command:?}, expected `schema` or `show`").into(),
+            );
+        }
+        None => return Err("missing config command, expected `schema` or `show`".into()),
+    }
+    Ok(())
+}
+
+/// prints the configuration of `file`, or of stdin, as TOML with where each setting
+/// comes from:
+///
+/// ```text
+/// # configuration of slides/a.typ
+/// indent_space = 4         # typstfmt.toml
+/// max_line_length = 120    # override ["slides/**"] of typstfmt.toml
+/// line_wrap = true         # default
+/// ```
+fn show_config(mut resolver: ConfigResolver, file: Option<&Path>) {
+    let resolved = resolver.explain(file);
+    for warning in resolver.take_warnings() {
+        eprintln!("Warning! {warning}");
+    }
+    let mut resolved = resolved.unwrap_or_else(|err| config_error(&err));
+    if let Some(file) = file.filter(|file| file.is_file()) {
+        let content = std::fs::read_to_string(file).unwrap_or_else(|err| {
+            eprintln!("{}", IoError::new("read", file, err));
+            std::process::exit(EXIT_IO_ERROR)
+        });
+        resolved = resolved.with_pragmas(&content).unwrap_or_else(|mut err| {
+            err.path = Some(file.to_path_buf());
+            config_error(&err)
+        });
+    }
+    let lines = resolved
+        .settings()
+        .into_iter()
+        .map(|(name, value, source)| (format!("{name} = {value}"), source))
+        .collect::<Vec<_>>();
+    let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+    let name = file.map_or_else(|| "stdin".into(), Path::to_string_lossy);
+    println!("# configuration of {name}");
+    for (line, source) in lines {
+        println!("{line:width$}  # {source}");
+    }
+}
+
+/// parses `START:END` into a range of lines.
+fn parse_lines(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got {s:?}"))?;
+    let start: usize = start.parse().map_err(|e| format!("invalid START: {e}"))?;
+    let end: usize = end.parse().map_err(|e| format!("invalid END: {e}"))?;
+    if start == 0 || end < start {
+        return Err(format!("invalid line range {s:?}, lines start at 1"));
+    }
+    Ok(start..=end)
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        eprintln!("use -h or --help");
+        std::process::exit(EXIT_USAGE_ERROR);
+    }
+}
+
+/// runs the command, an invalid command line is returned before anything is formatted.
+fn run() -> Result<(), lexopt::Error> {
+    if std::env::args_os().nth(1).is_some_and(|arg| arg == "lsp") {
+        let resolver = RefCell::new(ConfigResolver::new());
+        typstfmt::lsp::serve(stdin().lock(), stdout().lock(), |path| {
+            load_config(&mut resolver.borrow_mut(), path, |err| {
+                print_config_error(err);
+                Config::default()
+            })
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("the language server stopped: {err}");
+            std::process::exit(EXIT_IO_ERROR)
+        });
+        return Ok(());
+    }
+
+    if std::env::args_os()
+        .nth(1)
+        .is_some_and(|arg| arg == "config")
+    {
+        return config_command(std::env::args_os().skip(2));
+    }
+
     let mut parser = lexopt::Parser::from_env();
     let mut inputs = Inputs::Stdin;
-    let mut output = Output::None;
+    let mut output = Output::None(None);
+    let mut backup = None;
+    let mut stdin_filepath = None;
+    let mut changes = None;
     let mut verbose = false;
+    let mut lines = None;
+    let mut verify = false;
+    let mut diff = false;
+    let mut color = ColorChoice::Auto;
+    let mut jobs = thread::available_parallelism().map_or(1, usize::from);
+    let mut config_options = ConfigOptions::default();
     while let Some(arg) = parser.next()? {
         match arg {
             Long("version") | Short('v') => {
@@ -200,85 +774,191 @@ fn main() -> Result<(), lexopt::Error> {
                     Output::File(value)
                 };
             }
+            Long("lines") => {
+                lines = Some(parser.value()?.parse_with(parse_lines)?);
+            }
+            Long("verify") => {
+                verify = true;
+            }
+            Long(name) if ConfigOptions::FLAGS.contains(&name) => {
+                let name = name.to_string();
+                config_options.parse(&name, &mut parser)?;
+            }
+            Long("jobs") | Short('j') => {
+                jobs = parser.value()?.parse_with(|s| match s.parse() {
+                    Ok(0) => Err("there must be at least one job".to_string()),
+                    Ok(jobs) => Ok(jobs),
+                    Err(err) => Err(format!("invalid number of jobs: {err}")),
+                })?;
+            }
             Long("verbose") => {
                 verbose = true;
             }
             Long("check") => {
                 output = Output::Check;
             }
-            _ => {
-                println!("{}", arg.unexpected());
-                println!("use -h or --help");
-                return Ok(());
+            Long("report-format") => {
+                output = Output::Report(parser.value()?.parse()?);
+            }
+            Long("changed-since") => {
+                changes = Some(Changes::Since(parser.value()?.string()?));
+            }
+            Long("staged") => {
+                changes = Some(Changes::Staged);
+            }
+            Long("stdin-filepath") => {
+                stdin_filepath = Some(PathBuf::from(parser.value()?));
+            }
+            Long("backup") => {
+                backup = Some(parser.optional_value().unwrap_or_else(|| "~".into()));
+            }
+            Long("diff") => {
+                diff = true;
+            }
+            Long("color") => {
+                color = parser.value()?.parse_with(ColorChoice::parse)?;
             }
+            _ => return Err(arg.unexpected()),
         }
     }
 
-    if matches!(inputs, Inputs::Stdin) && matches!(output, Output::None) {
+    if diff {
+        output = Output::Diff(color.enabled());
+    }
+    if matches!(inputs, Inputs::Stdin) && matches!(output, Output::None(_)) {
         output = Output::Stdout;
     }
+    if backup.is_some() && !matches!(output, Output::None(_)) {
+        return Err(
+            "--backup only applies when files are overwritten, not with stdin, \
+            --output, --check, --diff or --report-format"
+                .into(),
+        );
+    }
+    if let Output::None(suffix) = &mut output {
+        *suffix = backup;
+    }
 
-    let config = {
-        let open_config = |file_name| File::options().read(true).open(file_name);
-        let config = open_config(CONFIG_FILE_NAME);
-        let dot_config_file_name = format!(".{CONFIG_FILE_NAME}");
-        let dot_config = open_config(&dot_config_file_name);
-        let is_config_ok = config.is_ok();
-        if is_config_ok && dot_config.is_ok() {
-            eprintln!(
-                "Warning! Both {first:?} and {second:?} are present. Using {first:?}.",
-                first = CONFIG_FILE_NAME,
-                second = dot_config_file_name
-            );
-        }
-        if let Ok(mut f) = config.or(dot_config) {
-            let mut buf = String::default();
-            let used_config_file_name = if is_config_ok {
-                CONFIG_FILE_NAME
-            } else {
-                &dot_config_file_name
-            };
-            f.read_to_string(&mut buf).unwrap_or_else(|err| {
-                panic!("Failed to read config file {used_config_file_name:?}: {err}");
-            });
-            Config::from_toml(&buf).unwrap_or_else(|err| {
-                panic!(
-                    "Config file {used_config_file_name:?} is invalid: {err}.\n{}",
-                    "You'll maybe have to delete it and use -C to create a default config file."
-                )
-            })
-        } else {
-            let config_path = confy::get_configuration_file_path(APP_NAME, APP_NAME)
-                .unwrap_or_else(|e| panic!("Error loading global configuration file: {e}"));
-            confy::load(APP_NAME, APP_NAME).unwrap_or_else(|e| {
-                panic!(
-                    "Error loading global configuration file at {}: {e}",
-                    config_path.display()
-                )
-            })
-        }
-    };
+    if stdin_filepath.is_some() && !matches!(inputs, Inputs::Stdin) {
+        return Err("--stdin-filepath only applies when reading stdin, not with files".into());
+    }
+
+    if changes.is_some() && matches!(inputs, Inputs::Stdin) {
+        return Err("--changed-since and --staged need files, not stdin".into());
+    }
+    if changes.is_some() && lines.is_some() {
+        return Err("--lines can't be used with --changed-since or --staged".into());
+    }
+
+    let mut resolver = config_options.resolver();
 
     let mut exit_status = 0;
+    if let Inputs::Files(paths) = inputs {
+        inputs = Inputs::Files(expand_dirs(paths, &mut resolver, &mut exit_status));
+    }
 
-    match &inputs {
-        Inputs::Stdin => {}
-        Inputs::Files(paths) => {
-            assert!(
-                !(matches!(output, Output::File(_)) && paths.len() > 1),
-                "You specified multiple inputs and --output but one output file cannot receive the result of many files.\nAborting."
-    );
+    if let (Inputs::Files(paths), Output::File(_)) = (&inputs, &output) {
+        if paths.len() > 1 {
+            let count = paths.len();
+            return Err(format!("--output takes a single input, not {count} files").into());
         }
     }
 
-    for input in inputs.read() {
-        let formatted = format(&input.content, config);
+    // configurations are resolved first, an invalid one stops everything before any write.
+    let inputs = inputs
+        .paths()
+        .into_iter()
+        .map(|path| {
+            let config_path = path.as_deref().map(Path::new).or(stdin_filepath.as_deref());
+            let config = load_config(&mut resolver, config_path, |err| config_error(err));
+            (path, config)
+        })
+        .collect::<Vec<_>>();
 
-        match output.write(&input, &formatted, verbose) {
-            Ok(()) => {}
-            Err(()) => {
-                exit_status = 1;
+    // stdin is echoed untouched when its virtual path is ignored, like the file would be skipped.
+    let stdin_skipped = match &stdin_filepath {
+        Some(path) => is_skipped(path, &mut resolver).unwrap_or_else(|err| match err {
+            WalkError::Config(err) => config_error(&err),
+            WalkError::Io(..) => {
+                eprintln!("{err}");
+                exit_status = EXIT_IO_ERROR;
+                false
+            }
+        }),
+        None => false,
+    };
+    let format = |(path, config): &(Option<OsString>, Config)| {
+        let mut input = Input::read(path.as_ref())?;
+        if let (None, Some(name)) = (path, &stdin_filepath) {
+            input.name = relative_path(name);
+            if stdin_skipped {
+                let content = input.content.clone();
+                return Ok((input, Outcome::Formatted(content)));
+            }
+        }
+        let lines = match (&changes, path) {
+            (Some(changes), Some(path)) => match changes.lines(Path::new(path)) {
+                Ok(changed) => Some(changed),
+                Err(err) => return Ok((input, Outcome::Git(err))),
+            },
+            _ => lines.clone().map(|lines| vec![lines]),
+        };
+        let outcome = format_input(&input, *config, verify, lines.as_deref());
+        Ok((input, outcome))
+    };
+    let mut reports = vec![];
+    map_in_order(&inputs, jobs, format, |read: Result<_, IoError>| {
+        let (input, outcome) = match read {
+            Ok(read) => read,
+            Err(err) => {
+                eprintln!("{err}");
+                if let Output::Report(_) = output {
+                    reports.push(FileReport::error(relative_path(&err.path), err.to_string()));
+                }
+                exit_status = exit_status.max(EXIT_IO_ERROR);
+                return;
+            }
+        };
+        if let Output::Report(_) = output {
+            let path = diff_path(&input);
+            reports.push(match &outcome {
+                Outcome::Formatted(formatted) => FileReport::new(path, &input.content, formatted),
+                Outcome::InvalidPragma(err) => FileReport::error(path, err.to_string()),
+                Outcome::Failed(err) => FileReport::error(path, err.to_string()),
+                Outcome::Git(err) => FileReport::error(path, err.to_string()),
+            });
+        }
+        let code = match outcome {
+            Outcome::Formatted(formatted) => match output.write(&input, &formatted, verbose) {
+                Ok(Written::Done) => 0,
+                Ok(Written::NeedsFormatting) => EXIT_NEEDS_FORMATTING,
+                Err(err) => {
+                    eprintln!("{err}");
+                    EXIT_IO_ERROR
+                }
+            },
+            Outcome::InvalidPragma(err) => {
+                print_config_error(&err);
+                EXIT_CONFIG_ERROR
+            }
+            Outcome::Failed(err) => {
+                eprintln!("{}: {err}", input.name);
+                match err {
+                    FormatError::Parse(_) => EXIT_PARSE_ERROR,
+                    FormatError::Verification(_) => EXIT_VERIFY_FAILED,
+                }
+            }
+            Outcome::Git(err) => {
+                eprintln!("{}: {err}", input.name);
+                EXIT_IO_ERROR
             }
+        };
+        exit_status = exit_status.max(code);
+    });
+    if let Output::Report(format) = output {
+        if let Err(err) = stdout().write_all(format.render(&reports).as_bytes()) {
+            eprintln!("{}", IoError::new("write to", "stdout", err));
+            exit_status = exit_status.max(EXIT_IO_ERROR);
         }
     }
     if exit_status == 0 {
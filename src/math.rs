@@ -1,11 +1,10 @@
 use super::*;
 use crate::context::Ctx;
 use crate::format_comment_handling_disable;
-use unicode_width::UnicodeWidthStr as _;
 
 #[instrument(skip_all)]
-pub(crate) fn format_equation(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
+pub(crate) fn format_equation(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
     let first_space = parent.children().nth(1);
     let space_type = if first_space
         .as_ref()
@@ -20,46 +19,51 @@ pub(crate) fn format_equation(parent: &LinkedNode, children: &[String], ctx: &mu
 
     let newline = space_type == "\n";
 
-    for (s, node) in children.iter().zip(parent.children()) {
+    for (s, node) in children.into_iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             LineComment | BlockComment => {
-                let buf = format_comment_handling_disable(&node, &[], ctx);
+                let buf = format_comment_handling_disable(&node, ctx);
                 ctx.push_raw_in(&buf, &mut res);
             }
             Dollar if first_dollar => {
                 first_dollar = false;
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
             Math => {
                 if newline {
                     ctx.push_raw_in(ctx.get_indent().as_str(), &mut res);
-                    ctx.push_raw_indent(s, &mut res);
+                    ctx.push_doc_indent(s, &mut res);
                 } else {
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                 }
             }
             Space => {
                 ctx.push_raw_in(space_type, &mut res);
             }
-            _ => ctx.push_raw_indent(s, &mut res),
+            _ => ctx.push_doc_indent(s, &mut res),
         }
     }
-    res
+    Doc::concat(res)
 }
 
 #[instrument(skip_all)]
-pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
+pub(crate) fn format_math(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
 
-    let align_columns = retrieve_align_columns(parent, children);
+    // aligning needs the width of the children, they're measured on their own.
+    let rendered = children
+        .iter()
+        .map(|child| child.render(&ctx.config))
+        .collect_vec();
+    let align_columns = retrieve_align_columns(parent, &rendered, &ctx.config);
     let mut index = 0;
     let mut position = 0usize;
 
     let mut first_align = true;
     let mut should_indent = false;
 
-    for (s, node) in children.iter().zip(parent.children()) {
+    for ((doc, s), node) in children.into_iter().zip(&rendered).zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             MathAlignPoint => {
@@ -82,8 +86,8 @@ pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ct
                     " ".repeat(align_columns[index] - position).as_str(),
                     &mut res,
                 );
-                ctx.push_raw_in(s, &mut res);
-                position = align_columns[index] + s.width();
+                ctx.push_doc(doc, &mut res);
+                position = align_columns[index] + utils::str_width(s, ctx.config.indent_space);
                 index += 1;
 
                 first_align = false;
@@ -91,25 +95,25 @@ pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ct
             Space if s.contains('\n') => {
                 position = 0;
                 index = 0;
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(doc, &mut res);
             }
             Space => {
                 position += 1;
                 ctx.push_raw_in(" ", &mut res);
             }
             _ => {
-                position += s.width();
-                ctx.push_raw_in(s, &mut res)
+                position += utils::str_width(s, ctx.config.indent_space);
+                ctx.push_doc(doc, &mut res)
             }
         }
     }
 
-    res
+    Doc::concat(res)
 }
 
 /// Calculate the columns the alignment points in the math block should be placed at.
 /// The n-th alignment point on a line should be placed at `align_columns[n]`.
-fn retrieve_align_columns(parent: &LinkedNode, children: &[String]) -> Vec<usize> {
+fn retrieve_align_columns(parent: &LinkedNode, children: &[String], config: &Config) -> Vec<usize> {
     let mut align_columns: Vec<usize> = vec![];
     let mut index = 0;
     let mut position = 0usize;
@@ -143,7 +147,7 @@ fn retrieve_align_columns(parent: &LinkedNode, children: &[String]) -> Vec<usize
                 position += 1;
             }
             _ => {
-                position += s.width();
+                position += utils::str_width(s, config.indent_space);
             }
         }
     }
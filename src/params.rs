@@ -4,21 +4,10 @@ use super::*;
 use crate::utils::{get_next_ignoring, next_is_ignoring, Btype};
 
 #[instrument(skip_all)]
-/// format args using [format_args_tight] or [format_args_breaking] depending on the context.
+/// format args on one line if they fit, breaking after the opening parenthesis otherwise.
 /// - if number of args is 0, format tight.
-/// - if line gets above max_length - 7 in tight mode, format breaking. (see TODO: why plus 7)
-pub(crate) fn format_args(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    // check if any children is markup and contains a linebreak, if so, breaking
-    // let mut res = vec![];
-    // utils::find_children(&mut res, parent, &|c| {
-    //     c.parent_kind() == Some(Markup)
-    //         && (c.kind() == Parbreak || (c.kind() == Space) && c.text().contains('\n'))
-    // });
-
-    // if !res.is_empty() {
-    //     return format_args_breaking(parent, children, ctx);
-    // }
-
+/// - if there is a line comment, format breaking.
+pub(crate) fn format_args(parent: &LinkedNode, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
     if parent.children().any(|c| c.kind() == LineComment) {
         return format_args_breaking(parent, children, ctx);
     }
@@ -47,30 +36,129 @@ pub(crate) fn format_args(parent: &LinkedNode, children: &[String], ctx: &mut Ct
         return format_args_tight(parent, children, ctx);
     }
 
-    let res = format_args_tight(parent, children, ctx);
-    // TODO: why plus 7
-    // why plus 7? if you remove it you'll notice the official example
-    // fails, since the inner line is broken before reaching the limit,
-    // it's difficult to have a condition like "if one of my child had
-    // to break in order to not go over the max_len, break" So I had to
-    // resort to this hack. A more meaningful approach is desired.
-    if utils::max_line_length(&res) + 7 >= ctx.config.max_line_length {
-        return format_args_breaking(parent, children, ctx);
+    if parent.children().any(|c| c.kind() == BlockComment) {
+        // comments aren't placed the same way when breaking.
+        let tight = format_args_tight(parent, children.clone(), ctx);
+        let breaking = format_args_breaking(parent, children, ctx);
+        return Doc::group(Doc::if_break(breaking, tight));
     }
-    res
+
+    format_args_group(parent, children, ctx)
 }
 
-pub(crate) fn format_args_tight(
-    parent: &LinkedNode<'_>,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
+/// the args on one line, or on an indented line of their own between the parenthesis
+/// if they don't fit.
+///
+/// With [Config::experimental_args_breaking_consecutive] the args are broken on
+/// as many lines as needed, at most 4 by line.
+fn format_args_group(parent: &LinkedNode<'_>, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
+    // what's between the parenthesis, contents and separators with the experimental flag.
+    let mut args: Vec<Doc> = vec![];
+    let mut in_parenthesis = false;
+    let experimental = ctx.config.experimental_args_breaking_consecutive;
+    let is_destruct_and_one_arg = typst_syntax::ast::Destructuring::from_untyped(parent)
+        .is_some_and(|x| x.bindings().count() == 1);
+    let is_block_math = utils::block_type(parent) == Btype::Math;
+    let is_parenthesized = parent.kind() == Parenthesized;
+    let mut missing_trailing = is_destruct_and_one_arg;
+    let mut missing_trailing_comma = !(is_parenthesized || is_block_math);
+    let mut consecutive_items = 0;
+
+    for (s, node) in children.into_iter().zip(parent.children()) {
+        let is_last = utils::next_is_ignoring(&node, RightParen, &[Space]);
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            LeftParen => {
+                in_parenthesis = true;
+                ctx.push_doc(s, &mut res);
+            }
+            RightParen => {
+                in_parenthesis = false;
+                let args = std::mem::take(&mut args);
+                let args = if experimental {
+                    Doc::Fill(args)
+                } else {
+                    Doc::concat(args)
+                };
+                res.push(Doc::indent_if_break(Doc::concat(vec![Doc::SoftLine, args])));
+                res.push(Doc::SoftLine);
+                ctx.push_doc(s, &mut res);
+            }
+            Space => {}
+            // trailing content blocks
+            _ if !in_parenthesis && node.kind() != Comma => ctx.push_doc(s, &mut res),
+            Comma => {
+                let is_last_comma = utils::find_next(&node, &|x| x.kind() == Comma).is_none();
+                let is_trailing = utils::next_is_ignoring(&node, RightParen, &[Space]);
+                missing_trailing = is_last_comma && !is_trailing;
+                missing_trailing_comma = missing_trailing;
+                // math arrays are separated by commas without parenthesis.
+                let args = if in_parenthesis { &mut args } else { &mut res };
+                let fill = experimental && in_parenthesis;
+
+                if is_trailing {
+                    // not putting the comma in would result in a parenthesized expression, not an array
+                    // "(a,) != (a)"
+                    let flat = if parent.kind() == Array || is_destruct_and_one_arg {
+                        s.clone()
+                    } else {
+                        Doc::Nil
+                    };
+                    ctx.lost_context();
+                    push_in_last(args, Doc::if_break(s, flat), fill);
+                } else if fill {
+                    ctx.lost_context();
+                    push_in_last(args, s, fill);
+                    consecutive_items += 1;
+                    if consecutive_items > 3 {
+                        consecutive_items = 0;
+                        args.push(Doc::if_break(Doc::HardLine, Doc::text(" ")));
+                    } else {
+                        args.push(Doc::Line);
+                    }
+                } else {
+                    ctx.push_doc(s, args);
+                    ctx.push_in(" ", args);
+                }
+            }
+            _ => {
+                ctx.push_doc(s, &mut args);
+                if is_last && missing_trailing_comma {
+                    let flat = if missing_trailing && is_destruct_and_one_arg {
+                        Doc::text(",")
+                    } else {
+                        Doc::Nil
+                    };
+                    push_in_last(&mut args, Doc::if_break(Doc::text(","), flat), experimental);
+                } else if is_last && missing_trailing && is_destruct_and_one_arg {
+                    push_in_last(&mut args, Doc::text(","), experimental);
+                }
+            }
+        }
+    }
+    Doc::group(Doc::concat(res))
+}
+
+/// pushes in the last content of the args, keeping contents and separators
+/// alternating for a [Doc::Fill].
+fn push_in_last(args: &mut Vec<Doc>, doc: Doc, fill: bool) {
+    match args.pop() {
+        Some(last) if fill => args.push(Doc::concat(vec![last, doc])),
+        last => {
+            args.extend(last);
+            args.push(doc);
+        }
+    }
+}
+
+pub(crate) fn format_args_tight(parent: &LinkedNode<'_>, children: Vec<Doc>, ctx: &mut Ctx) -> Doc {
+    let mut res = vec![];
     let is_destruct_and_one_arg = typst_syntax::ast::Destructuring::from_untyped(parent)
         .is_some_and(|x| x.bindings().count() == 1);
     let mut missing_trailing = is_destruct_and_one_arg;
 
-    for (s, node) in children.iter().zip(parent.children()) {
+    for (s, node) in children.into_iter().zip(parent.children()) {
         let is_last =
             utils::next_is_ignoring(&node, RightParen, &[Space, LineComment, BlockComment]);
 
@@ -90,19 +178,19 @@ pub(crate) fn format_args_tight(
                         ctx.push_raw_in(",", &mut res);
                     }
                 } else {
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                     ctx.push_in(" ", &mut res);
                 }
             }
             _ => {
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 if is_last && missing_trailing && is_destruct_and_one_arg {
                     ctx.push_raw_in(",", &mut res);
                 }
             }
         }
     }
-    res
+    Doc::concat(res)
 }
 
 #[derive(Debug, Default)]
@@ -119,10 +207,10 @@ impl TrailingBlockDetect {
 
 pub(crate) fn format_args_breaking(
     parent: &LinkedNode<'_>,
-    children: &[String],
+    children: Vec<Doc>,
     ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
+) -> Doc {
+    let mut res = vec![];
     let mut is_trailing_block = TrailingBlockDetect::default();
     let is_block_math = utils::block_type(parent) == Btype::Math;
     let is_parenthesized = parent.kind() == Parenthesized;
@@ -130,14 +218,14 @@ pub(crate) fn format_args_breaking(
     // only used with experimental flag in config for now
     let mut consecutive_items = 0;
 
-    for (s, node) in children.iter().zip(parent.children()) {
+    for (s, node) in children.into_iter().zip(parent.children()) {
         let is_last =
             utils::next_is_ignoring(&node, RightParen, &[Space, LineComment, BlockComment]);
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             LeftParen => {
                 is_trailing_block.left_par = true;
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
                 ctx.push_raw_in("\n", &mut res);
                 ctx.push_raw_in(&ctx.get_indent(), &mut res);
             }
@@ -147,30 +235,29 @@ pub(crate) fn format_args_breaking(
                     // no trailing comma we don't have a newline!
                     ctx.push_in("\n", &mut res);
                 }
-                ctx.push_raw_in(s, &mut res);
+                ctx.push_doc(s, &mut res);
             }
             LineComment | BlockComment => {
                 consecutive_items = 0;
                 if utils::prev_is_ignoring(&node, LineComment, &[Space])
                     || utils::prev_is_ignoring(&node, BlockComment, &[Space])
                 {
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                     ctx.push_in("\n", &mut res);
                 } else {
                     let prev = node.prev_sibling().unwrap();
-                    let mark = res.rfind(|x| x != ' ' && x != '\n').unwrap() + 1;
                     let prev_maybe_space = get_next_ignoring(&prev, &[]);
-                    res = res[..mark].to_string();
+                    doc::trim_end(&mut res);
 
                     match prev_maybe_space {
                         Some(space) if space.kind() == Space && space.text().contains('\n') => {
                             ctx.push_raw_in("\n", &mut res);
                             ctx.push_raw_in(&ctx.get_indent(), &mut res);
-                            ctx.push_raw_in(s, &mut res);
+                            ctx.push_doc(s, &mut res);
                         }
                         _ => {
                             ctx.push_raw_in(" ", &mut res);
-                            ctx.push_raw_in(s, &mut res);
+                            ctx.push_doc(s, &mut res);
                         }
                     }
                     ctx.push_raw_in("\n", &mut res);
@@ -193,39 +280,33 @@ pub(crate) fn format_args_breaking(
 
                 if is_last_comma && is_trailing {
                     // no indent
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                     ctx.push_raw_in("\n", &mut res);
                 } else if !ctx.config.experimental_args_breaking_consecutive {
-                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_doc(s, &mut res);
                     ctx.push_raw_in(" ", &mut res);
                 } else {
                     // experimental_args_breaking_consecutive
-                    if consecutive_items >= 3
-                        || s.contains('\n')
-                        || res.lines().last().is_some_and(|line| {
-                            utils::max_line_length(&format!("{line}, "))
-                                >= ctx.config.max_line_length
-                        })
-                    {
-                        ctx.push_raw_in(s, &mut res);
+                    if consecutive_items >= 3 || s.has_newline() {
+                        ctx.push_doc(s, &mut res);
                         ctx.push_raw_in("\n", &mut res);
                         ctx.push_raw_in(&ctx.get_indent(), &mut res);
                         consecutive_items = 0;
                     } else {
                         consecutive_items += 1;
-                        ctx.push_raw_in(s, &mut res);
+                        ctx.push_doc(s, &mut res);
                         ctx.push_raw_in(" ", &mut res);
                     }
                 }
             }
-            ContentBlock if is_trailing_block.is_trailing_block() => ctx.push_raw_in(s, &mut res),
+            ContentBlock if is_trailing_block.is_trailing_block() => ctx.push_doc(s, &mut res),
             _ => {
-                ctx.push_raw_indent(s, &mut res);
+                ctx.push_doc_indent(s, &mut res);
                 if is_last && missing_trailing_comma {
                     ctx.push_raw_in(",\n", &mut res);
                 }
             }
         }
     }
-    res
+    Doc::concat(res)
 }
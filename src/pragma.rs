@@ -0,0 +1,80 @@
+//! Comments changing how a document is formatted.
+use super::*;
+
+/// A comment understood by typstfmt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pragma<'a> {
+    /// `// typstfmt::off`, what follows is left untouched.
+    Off,
+    /// `// typstfmt::on`, what follows is formatted again.
+    On,
+    /// `// typstfmt: max_line_length=120, line_wrap=false`, settings for the whole document
+    /// when at the top of it. holds the text after the colon.
+    Settings(&'a str),
+}
+
+impl<'a> Pragma<'a> {
+    /// the pragma of the line comment `comment`, if any.
+    pub(crate) fn parse(comment: &'a str) -> Option<Self> {
+        if comment.contains("typstfmt::off") {
+            return Some(Pragma::Off);
+        }
+        if comment.contains("typstfmt::on") {
+            return Some(Pragma::On);
+        }
+        let settings = comment
+            .strip_prefix("//")?
+            .trim_start()
+            .strip_prefix("typstfmt:")?;
+        (!settings.starts_with(':')).then_some(Pragma::Settings(settings))
+    }
+}
+
+/// The settings of the pragmas at the top of `input`, before anything but blank lines
+/// and other line comments, later ones win.
+///
+/// Errors point at the setting at fault in `input`.
+pub(crate) fn settings(input: &str) -> Result<toml::Table, ConfigError> {
+    let mut settings = toml::Table::new();
+    let mut offset = 0;
+    for line in input.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let comment = line.trim();
+        if comment.is_empty() {
+            continue;
+        }
+        if !comment.starts_with("//") {
+            break;
+        }
+        let Some(Pragma::Settings(text)) = Pragma::parse(comment) else {
+            continue;
+        };
+        // `text` is a slice of `line`.
+        let text_offset = line_offset + (text.as_ptr() as usize - line.as_ptr() as usize);
+        for setting in text.split(',') {
+            let trimmed = setting.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let start =
+                text_offset + (setting.as_ptr() as usize - text.as_ptr() as usize) + setting.len()
+                    - setting.trim_start().len();
+            let parsed = config::parse_setting(trimmed).map_err(|mut err| {
+                err.message = format!("{} in a typstfmt pragma", err.message);
+                err.text = input.to_string();
+                err.at(Some(start..start + trimmed.len()))
+            })?;
+            settings.extend(parsed);
+        }
+    }
+    Ok(settings)
+}
+
+/// `config` with the pragmas of `input`, invalid pragmas are ignored with a warning.
+pub(crate) fn apply(input: &str, config: Config) -> Config {
+    config.with_pragmas(input).unwrap_or_else(|err| {
+        warn!("ignoring the pragmas of the document: {err}");
+        config
+    })
+}
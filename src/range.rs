@@ -0,0 +1,206 @@
+use std::ops::{Range, RangeInclusive};
+
+use super::*;
+use crate::pragma::{self, Pragma};
+
+/// Formats only the smallest nodes enclosing `range` (in bytes), everything outside of
+/// them is left byte-identical.
+///
+/// If `range` falls into a sequence of items (Markup, Code or Math) only the items it
+/// touches are formatted, not the whole sequence.
+///
+/// With [Config::verify], the input is returned unchanged if formatting it would change it's meaning.
+#[must_use]
+pub fn format_range(s: &str, range: Range<usize>, config: Config) -> String {
+    format_ranges(s, &[range], config)
+}
+
+/// Like [format_range] but formats the nodes enclosing each of `ranges`, a node enclosing
+/// several of them is formatted once. Without ranges, the input is returned unchanged.
+#[must_use]
+pub fn format_ranges(s: &str, ranges: &[Range<usize>], config: Config) -> String {
+    let config = pragma::apply(s, config);
+    verify::checked(s, format_ranges_unverified(s, ranges, config), config)
+}
+
+/// Like [format_ranges] but refuses to format an input with syntax errors touching `ranges`,
+/// returning where they are instead, errors elsewhere are tolerated.
+///
+/// With [Config::verify], returns what changed if formatting would change the meaning of the input.
+pub fn try_format_ranges(
+    s: &str,
+    ranges: &[Range<usize>],
+    config: Config,
+) -> Result<String, FormatError> {
+    let config = pragma::apply(s, config);
+    let mut errors = vec![];
+    error::collect_errors(&LinkedNode::new(&parse(s)), s, &mut errors);
+    errors.retain(|error| {
+        ranges
+            .iter()
+            .any(|range| error.range.start <= range.end && range.start <= error.range.end)
+    });
+    if !errors.is_empty() {
+        return Err(FormatError::Parse(errors));
+    }
+    let formatted = format_ranges_unverified(s, ranges, config);
+    if config.verify {
+        if let Some(diagnostic) = verify::difference(s, &formatted) {
+            return Err(FormatError::Verification(diagnostic));
+        }
+    }
+    Ok(formatted)
+}
+
+/// formats the nodes enclosing `ranges` with `config`, its pragmas already applied.
+fn format_ranges_unverified(s: &str, ranges: &[Range<usize>], config: Config) -> String {
+    let eol = config.end_of_line.resolve(s);
+    // the input is parsed and formatted with `\n` line endings like [format] does, what's
+    // outside the targets is copied from `s`.
+    let normalized = &utils::normalize_line_endings(s);
+    let crlfs = s.match_indices("\r\n").map(|(i, _)| i).collect_vec();
+    // where the `\n` of each `\r\n` ends up in `normalized`.
+    let newlines = crlfs.iter().enumerate().map(|(k, i)| i - k).collect_vec();
+    let to_normalized = |offset: usize| offset - crlfs.partition_point(|&i| i < offset);
+    let to_original = |offset: usize| offset + newlines.partition_point(|&i| i < offset);
+
+    let root = parse(normalized);
+    let root = LinkedNode::new(&root);
+    let mut res = String::new();
+    let mut targets = ranges
+        .iter()
+        .flat_map(|range| targets(&root, to_normalized(range.start)..to_normalized(range.end)))
+        .collect::<Vec<_>>();
+    // nodes are either nested or apart, the outermost comes first and the ones inside it are
+    // skipped.
+    targets.sort_by_key(|target| (target.offset(), std::cmp::Reverse(target.range().end)));
+    targets.dedup_by_key(|target| target.range());
+    let mut last = 0;
+    for target in targets {
+        let target_range = target.range();
+        if target_range.start < last {
+            continue;
+        }
+        let mut ctx = Ctx::from_config(config);
+        ctx.off = off_at(&root, target_range.start);
+        let doc = visit(&target, &mut ctx);
+        let line_start = line_start(normalized, target_range.start);
+        let column = utils::str_width(
+            &normalized[line_start..target_range.start],
+            config.indent_space,
+        );
+        let formatted = doc::print(&doc, &config, line_indent(normalized, line_start), column);
+        res.push_str(&s[to_original(last)..to_original(target_range.start)]);
+        let formatted = utils::trim_trailing_spaces(&formatted);
+        res.push_str(&utils::with_line_endings(&formatted, eol));
+        last = target_range.end;
+    }
+    res.push_str(&s[to_original(last)..]);
+    res
+}
+
+/// Like [format_range] but selects lines, the first line being 1.
+#[must_use]
+pub fn format_lines(s: &str, lines: RangeInclusive<usize>, config: Config) -> String {
+    format_range(s, lines_to_range(s, lines), config)
+}
+
+/// Like [format_ranges] but selects lines, the first line being 1.
+#[must_use]
+pub fn format_line_ranges(s: &str, lines: &[RangeInclusive<usize>], config: Config) -> String {
+    format_ranges(s, &lines_to_ranges(s, lines), config)
+}
+
+/// Like [try_format_ranges] but selects lines, the first line being 1.
+pub fn try_format_line_ranges(
+    s: &str,
+    lines: &[RangeInclusive<usize>],
+    config: Config,
+) -> Result<String, FormatError> {
+    try_format_ranges(s, &lines_to_ranges(s, lines), config)
+}
+
+fn lines_to_ranges(s: &str, lines: &[RangeInclusive<usize>]) -> Vec<Range<usize>> {
+    lines
+        .iter()
+        .map(|lines| lines_to_range(s, lines.clone()))
+        .collect()
+}
+
+/// Converts an inclusive range of lines (the first line being 1) to a byte range.
+///
+/// Lines past the end of the input are clamped to its end.
+pub(crate) fn lines_to_range(s: &str, lines: RangeInclusive<usize>) -> Range<usize> {
+    let line_start = |line: usize| {
+        if line <= 1 {
+            return Some(0);
+        }
+        s.match_indices('\n').nth(line - 2).map(|(i, _)| i + 1)
+    };
+    let start = line_start(*lines.start()).unwrap_or(s.len());
+    let end = line_start(lines.end().saturating_add(1))
+        .map(|next| next - 1)
+        .unwrap_or(s.len());
+    start..end.max(start)
+}
+
+/// finds the nodes to format for a range.
+fn targets<'a>(root: &LinkedNode<'a>, range: Range<usize>) -> Vec<LinkedNode<'a>> {
+    let contains = |node: &LinkedNode| {
+        let node_range = node.range();
+        node_range.start <= range.start && range.end <= node_range.end
+    };
+    let mut node = root.clone();
+    while let Some(child) = node
+        .children()
+        .find(|c| contains(c) && c.children().next().is_some())
+    {
+        node = child;
+    }
+
+    let is_sequence = [Markup, Code, Math].contains(&node.kind());
+    let node_range = node.range();
+    let fully_selected = range.start <= node_range.start && node_range.end <= range.end;
+    if !is_sequence || fully_selected {
+        return vec![node];
+    }
+
+    node.children()
+        .filter(|c| ![Space, Parbreak].contains(&c.kind()))
+        .filter(|c| {
+            let child_range = c.range();
+            child_range.start < range.end.max(range.start + 1) && range.start < child_range.end
+        })
+        .collect()
+}
+
+/// tells if `typstfmt::off` is in effect at this offset,
+/// the last `typstfmt::on` or `typstfmt::off` comment before it decides.
+fn off_at(root: &LinkedNode, offset: usize) -> bool {
+    fn visit_comments(node: &LinkedNode, offset: usize, off: &mut bool) {
+        for child in node.children().take_while(|c| c.offset() < offset) {
+            if child.kind() == LineComment {
+                match Pragma::parse(child.text()) {
+                    Some(Pragma::Off) => *off = true,
+                    Some(Pragma::On) => *off = false,
+                    _ => {}
+                }
+            }
+            visit_comments(&child, offset, off);
+        }
+    }
+    let mut off = false;
+    visit_comments(root, offset, &mut off);
+    off
+}
+
+/// the offset of the start of the line containing `offset`.
+fn line_start(s: &str, offset: usize) -> usize {
+    s[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+/// the whitespace at the start of the line starting at `line_start`.
+fn line_indent(s: &str, line_start: usize) -> &str {
+    let line = &s[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
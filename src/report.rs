@@ -0,0 +1,281 @@
+//! Machine readable reports of the files needing formatting, for CI.
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+use serde_json::{json, Value};
+use similar::{DiffOp, TextDiff};
+
+/// The result of checking a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    /// The path of the file, relative with `/` separators preferably.
+    pub path: String,
+    /// The lines of the file formatting would change, 1-based, see [changed_lines].
+    pub changes: Vec<RangeInclusive<usize>>,
+    /// Why the file couldn't be formatted, if it couldn't.
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    /// The report of a file whose content is `original` and would be `formatted`.
+    #[must_use]
+    pub fn new(path: String, original: &str, formatted: &str) -> Self {
+        Self {
+            path,
+            changes: changed_lines(original, formatted),
+            error: None,
+        }
+    }
+
+    /// The report of a file which couldn't be formatted.
+    #[must_use]
+    pub fn error(path: String, error: String) -> Self {
+        Self {
+            path,
+            changes: vec![],
+            error: Some(error),
+        }
+    }
+
+    /// Tells if the file is formatted.
+    #[must_use]
+    pub fn is_formatted(&self) -> bool {
+        self.changes.is_empty() && self.error.is_none()
+    }
+}
+
+/// The lines of `original` changed in `formatted`, 1-based and merged when they touch.
+///
+/// Lines only inserted are reported on the line before them, the first line if there is none.
+#[must_use]
+pub fn changed_lines(original: &str, formatted: &str) -> Vec<RangeInclusive<usize>> {
+    let mut res: Vec<RangeInclusive<usize>> = vec![];
+    for op in TextDiff::from_lines(original, formatted).ops() {
+        let lines = match *op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Insert { old_index, .. } => old_index.max(1)..=old_index.max(1),
+            DiffOp::Delete {
+                old_index, old_len, ..
+            }
+            | DiffOp::Replace {
+                old_index, old_len, ..
+            } => old_index + 1..=old_index + old_len,
+        };
+        match res.last_mut() {
+            Some(last) if *lines.start() <= last.end() + 1 => {
+                *last = *last.start()..=*lines.end().max(last.end());
+            }
+            _ => res.push(lines),
+        }
+    }
+    res
+}
+
+/// A format of report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// `{"files": [{"path": ..., "formatted": ..., "changes": [{"start_line": ..., "end_line": ...}]}]}`
+    Json,
+    /// [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html),
+    /// understood by most code review tools.
+    Sarif,
+    /// The XML of checkstyle.
+    Checkstyle,
+    /// [Workflow commands](https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions)
+    /// of GitHub Actions, annotating the lines needing formatting.
+    Github,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "sarif" => Ok(ReportFormat::Sarif),
+            "checkstyle" => Ok(ReportFormat::Checkstyle),
+            "github" => Ok(ReportFormat::Github),
+            _ => Err(format!(
+                "unknown report format {s:?}, expected json, sarif, checkstyle or github"
+            )),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Renders the reports of the checked files.
+    #[must_use]
+    pub fn render(self, reports: &[FileReport]) -> String {
+        match self {
+            ReportFormat::Json => {
+                let mut json = serde_json::to_string_pretty(&json_report(reports)).unwrap();
+                json.push('\n');
+                json
+            }
+            ReportFormat::Sarif => {
+                let mut sarif = serde_json::to_string_pretty(&sarif_report(reports)).unwrap();
+                sarif.push('\n');
+                sarif
+            }
+            ReportFormat::Checkstyle => checkstyle_report(reports),
+            ReportFormat::Github => github_report(reports),
+        }
+    }
+}
+
+/// what is wrong with some lines.
+fn message(lines: &RangeInclusive<usize>) -> String {
+    if lines.start() == lines.end() {
+        format!("line {} needs formatting", lines.start())
+    } else {
+        format!("lines {}-{} need formatting", lines.start(), lines.end())
+    }
+}
+
+fn json_report(reports: &[FileReport]) -> Value {
+    let files = reports
+        .iter()
+        .map(|report| {
+            let changes = report
+                .changes
+                .iter()
+                .map(|lines| json!({ "start_line": lines.start(), "end_line": lines.end() }))
+                .collect::<Vec<_>>();
+            let mut file = json!({
+                "path": report.path,
+                "formatted": report.is_formatted(),
+                "changes": changes,
+            });
+            if let Some(error) = &report.error {
+                file["error"] = error.as_str().into();
+            }
+            file
+        })
+        .collect::<Vec<_>>();
+    json!({ "files": files })
+}
+
+fn sarif_report(reports: &[FileReport]) -> Value {
+    let location = |path: &str, lines: Option<&RangeInclusive<usize>>| {
+        let mut location = json!({ "artifactLocation": { "uri": path } });
+        if let Some(lines) = lines {
+            location["region"] = json!({ "startLine": lines.start(), "endLine": lines.end() });
+        }
+        json!([{ "physicalLocation": location }])
+    };
+    let mut results = vec![];
+    for report in reports {
+        if let Some(error) = &report.error {
+            results.push(json!({
+                "ruleId": "error",
+                "level": "error",
+                "message": { "text": error },
+                "locations": location(&report.path, None),
+            }));
+        }
+        for lines in &report.changes {
+            results.push(json!({
+                "ruleId": "needs-formatting",
+                "level": "warning",
+                "message": { "text": message(lines) },
+                "locations": location(&report.path, Some(lines)),
+            }));
+        }
+    }
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "typstfmt",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": [
+                        {
+                            "id": "needs-formatting",
+                            "shortDescription": { "text": "Formatting would change these lines." },
+                        },
+                        {
+                            "id": "error",
+                            "shortDescription": { "text": "The file couldn't be formatted." },
+                        },
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn checkstyle_report(reports: &[FileReport]) -> String {
+    let mut res = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    res.push_str("<checkstyle version=\"4.3\">\n");
+    for report in reports {
+        let _ = writeln!(res, "  <file name=\"{}\">", xml_escape(&report.path));
+        if let Some(error) = &report.error {
+            let _ = writeln!(
+                res,
+                "    <error line=\"1\" severity=\"error\" message=\"{}\" source=\"typstfmt\"/>",
+                xml_escape(error)
+            );
+        }
+        for lines in &report.changes {
+            let _ = writeln!(
+                res,
+                "    <error line=\"{}\" severity=\"warning\" message=\"{}\" source=\"typstfmt\"/>",
+                lines.start(),
+                message(lines)
+            );
+        }
+        res.push_str("  </file>\n");
+    }
+    res.push_str("</checkstyle>\n");
+    res
+}
+
+fn github_report(reports: &[FileReport]) -> String {
+    let mut res = String::new();
+    for report in reports {
+        let file = github_escape(&report.path, true);
+        if let Some(error) = &report.error {
+            let _ = writeln!(
+                res,
+                "::error file={file},title=typstfmt::{}",
+                github_escape(error, false)
+            );
+        }
+        for lines in &report.changes {
+            let _ = writeln!(
+                res,
+                "::warning file={file},line={},endLine={},title=typstfmt::{}",
+                lines.start(),
+                lines.end(),
+                message(lines)
+            );
+        }
+    }
+    res
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+        .replace('\n', "&#10;")
+}
+
+/// escapes the message of a workflow command, or the value of one of its properties.
+fn github_escape(s: &str, property: bool) -> String {
+    let s = s
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    if property {
+        s.replace(':', "%3A").replace(',', "%2C")
+    } else {
+        s
+    }
+}
@@ -0,0 +1,159 @@
+//! The JSON Schema of configuration files, generated from [Config] and its doc comments.
+//!
+//! [Config] and the types of its settings are defined through [settings!] and
+//! [setting_enum!] which keep their doc comments, a new setting is in the schema without
+//! further work.
+use serde_json::{json, Map, Value};
+
+use super::*;
+
+/// The schema of the value of a setting.
+pub(crate) trait SettingSchema {
+    fn schema() -> Value;
+}
+
+impl SettingSchema for usize {
+    fn schema() -> Value {
+        json!({ "type": "integer", "minimum": 0 })
+    }
+}
+
+impl SettingSchema for bool {
+    fn schema() -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+/// defines a struct of settings, the name, doc comment and schema of each field are
+/// in `Self::SETTINGS`.
+macro_rules! settings {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                pub $field:ident: $ty:ty,
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        pub struct $name {
+            $(
+                $(#[doc = $doc])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl $name {
+            /// the name, description and schema of each setting.
+            pub(crate) const SETTINGS: &'static [(&'static str, &'static str, fn() -> serde_json::Value)] = &[
+                $((
+                    stringify!($field),
+                    concat!($($doc, "\n",)* ""),
+                    <$ty as $crate::schema::SettingSchema>::schema,
+                ),)*
+            ];
+        }
+    };
+}
+
+/// defines a unit enum whose variants are the string values of a setting.
+macro_rules! setting_enum {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident,
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        pub enum $name {
+            $(
+                $(#[$variant_attr])*
+                $variant,
+            )*
+        }
+
+        impl $crate::schema::SettingSchema for $name {
+            fn schema() -> serde_json::Value {
+                let variants = [$(serde_json::to_value($name::$variant).unwrap(),)*];
+                serde_json::json!({ "type": "string", "enum": variants })
+            }
+        }
+    };
+}
+
+pub(crate) use {setting_enum, settings};
+
+/// the doc comment of a setting as a description: its lines without the leading space, links to
+/// settings as code.
+pub(crate) fn description(doc: &str) -> String {
+    let doc = doc
+        .lines()
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    regex::Regex::new(r"\[Config::(\w+)\]")
+        .unwrap()
+        .replace_all(doc.trim(), "`$1`")
+        .into_owned()
+}
+
+/// The JSON Schema of configuration files, `typstfmt config schema` prints it.
+#[must_use]
+pub fn config_schema() -> Value {
+    let defaults = serde_json::to_value(Config::default()).unwrap();
+    let mut settings = Map::new();
+    for (name, doc, schema) in Config::SETTINGS {
+        let mut schema = schema();
+        schema["description"] = description(doc).into();
+        schema["default"] = defaults[name].clone();
+        settings.insert(name.to_string(), schema);
+    }
+    let globs = |description: &str| {
+        json!({
+            "type": "array",
+            "items": { "type": "string" },
+            "description": description,
+        })
+    };
+
+    let mut overrides = settings.clone();
+    for setting in overrides.values_mut() {
+        setting.as_object_mut().unwrap().remove("default");
+    }
+    overrides.insert(
+        "files".to_string(),
+        globs("The files the override applies to, globs relative to the configuration file."),
+    );
+    let mut properties = settings;
+    properties.insert(
+        "exclude".to_string(),
+        globs(
+            "Files skipped when searching a directory, globs relative to the configuration file.",
+        ),
+    );
+    properties.insert(
+        "overrides".to_string(),
+        json!({
+            "type": "array",
+            "description": "Settings for the files matching some globs, every matching override applies, in order.",
+            "items": {
+                "type": "object",
+                "properties": overrides,
+                "required": ["files"],
+                "additionalProperties": false,
+            },
+        }),
+    );
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "typstfmt.toml",
+        "description": "The configuration of typstfmt.",
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
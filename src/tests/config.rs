@@ -0,0 +1,71 @@
+use super::*;
+
+fn err(s: &str) -> ConfigError {
+    ConfigFile::from_toml(s).unwrap_err()
+}
+
+#[test]
+fn unknown_setting_suggests_a_known_one() {
+    let err = err("indent_space = 4\nmax_line_lenght = 100\n");
+    assert_eq!(err.message, "unknown setting `max_line_lenght`");
+    assert_eq!(err.key.as_deref(), Some("max_line_lenght"));
+    assert_eq!(err.suggestion.as_deref(), Some("max_line_length"));
+    assert_eq!(err.line_column(), Some((2, 1)));
+    assert_eq!(
+        err.to_string(),
+        "unknown setting `max_line_lenght`, did you mean `max_line_length`?"
+    );
+}
+
+#[test]
+fn unknown_setting_without_close_one() {
+    let err = err("colour = true");
+    assert_eq!(err.suggestion, None);
+}
+
+#[test]
+fn invalid_type_points_at_the_value() {
+    let err = err("line_wrap = true\nindent_space = \"4\"\n");
+    assert!(err.message.starts_with("invalid `indent_space`"), "{err}");
+    assert_eq!(err.line_column(), Some((2, 16)));
+}
+
+#[test]
+fn invalid_variant() {
+    let err = err("end_of_line = \"cr\"");
+    assert!(err.message.starts_with("invalid `end_of_line`"), "{err}");
+    assert_eq!(err.line_column(), Some((1, 15)));
+}
+
+#[test]
+fn invalid_values() {
+    let small = err("indent_space = 1");
+    assert_eq!(small.message, "indent_space must be at least 2, got 1");
+    assert_eq!(small.line_column(), Some((1, 16)));
+
+    let zero = err("max_line_length = 0");
+    assert_eq!(zero.message, "max_line_length can't be 0");
+    assert_eq!(zero.key.as_deref(), Some("max_line_length"));
+}
+
+#[test]
+fn invalid_override_value_points_at_its_line() {
+    let err = err("[[overrides]]\nfiles = [\"*.typ\"]\nindent_space = 0\n");
+    assert_eq!(err.key.as_deref(), Some("indent_space"));
+    assert_eq!(err.line_column(), Some((3, 16)));
+}
+
+#[test]
+fn syntax_error_has_a_location() {
+    let err = err("max_line_length = 80\nline_wrap = \n");
+    assert!(err.line_column().is_some());
+}
+
+#[test]
+fn in_file_shows_the_path() {
+    let err = err("indent_spaces = 4").in_file("typstfmt.toml".into(), "indent_spaces = 4");
+    assert_eq!(
+        err.to_string(),
+        "typstfmt.toml:1:1: unknown setting `indent_spaces`, did you mean `indent_space`?"
+    );
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+#[test]
+fn no_diff_when_formatted() {
+    assert_eq!(unified_diff("#f(a, b)\n", "#f(a, b)\n", "a.typ", true), "");
+}
+
+#[test]
+fn diff_headers_and_hunks() {
+    let original = "#f(a,b)\nsame\n";
+    similar_asserts::assert_eq!(
+        unified_diff(original, "#f(a, b)\nsame\n", "dir/a.typ", false),
+        "--- a/dir/a.typ\n+++ b/dir/a.typ\n@@ -1,2 +1,2 @@\n-#f(a,b)\n+#f(a, b)\n same\n"
+    );
+}
+
+#[test]
+fn diff_missing_newline() {
+    similar_asserts::assert_eq!(
+        unified_diff("#f(a,b)", "#f(a, b)", "a.typ", false),
+        "--- a/a.typ\n+++ b/a.typ\n@@ -1 +1 @@\n-#f(a,b)\n\\ No newline at end of file\n+#f(a, b)\n\\ No newline at end of file\n"
+    );
+}
+
+#[test]
+fn diff_context_is_limited() {
+    let lines = (0..20).map(|i| format!("line {i}\n")).collect::<String>();
+    let original = format!("#f(a,b)\n{lines}#g(c,d)\n");
+    let formatted = format!("#f(a, b)\n{lines}#g(c, d)\n");
+    let diff = unified_diff(&original, &formatted, "a.typ", false);
+    assert_eq!(diff.matches("@@ -").count(), 2, "{diff}");
+    assert!(!diff.contains("line 10"));
+}
+
+#[test]
+fn colored_diff() {
+    let diff = unified_diff("#f(a,b)\n", "#f(a, b)\n", "a.typ", true);
+    assert!(diff.contains("\x1b[31m-#f(a,b)\x1b[0m\n"), "{diff:?}");
+    assert!(diff.contains("\x1b[32m+#f(a, b)\x1b[0m\n"), "{diff:?}");
+    assert!(diff.starts_with("\x1b[1m--- a/a.typ\x1b[0m\n"), "{diff:?}");
+}
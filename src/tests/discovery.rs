@@ -0,0 +1,239 @@
+use std::fs;
+
+use super::*;
+
+#[test]
+fn closest_config_is_used() {
+    let root = temp_dir("closest");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join("typstfmt.toml"), "indent_space = 4");
+    write(&root.join("sub/.typstfmt.toml"), "indent_space = 8");
+    fs::create_dir_all(root.join("sub/deep")).unwrap();
+    fs::create_dir_all(root.join("other")).unwrap();
+
+    let mut resolver = ConfigResolver::new();
+    let config = resolver.config_for(Some(&root.join("sub/deep/a.typ")));
+    assert_eq!(config.unwrap().indent_space, 8);
+    let config = resolver.config_for(Some(&root.join("other/a.typ")));
+    assert_eq!(config.unwrap().indent_space, 4);
+    let config = resolver.config_for(Some(&root.join("a.typ")));
+    assert_eq!(config.unwrap().indent_space, 4);
+}
+
+#[test]
+fn search_stops_at_root_marker() {
+    let outer = temp_dir("root_marker");
+    write(&outer.join("typstfmt.toml"), "indent_space = 4");
+    write(&outer.join("project/typst.toml"), "");
+    fs::create_dir_all(outer.join("project/chapters")).unwrap();
+
+    let mut resolver = ConfigResolver::new();
+    assert_eq!(resolver.config_file(&outer.join("project/chapters")), None);
+    assert_eq!(
+        resolver.config_file(&outer.join("other")),
+        Some(outer.join("typstfmt.toml"))
+    );
+}
+
+#[test]
+fn lookups_are_cached() {
+    let root = temp_dir("cached");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join("typstfmt.toml"), "indent_space = 4");
+
+    let mut resolver = ConfigResolver::new();
+    let file = root.join("a.typ");
+    assert_eq!(resolver.config_for(Some(&file)).unwrap().indent_space, 4);
+    write(&root.join("typstfmt.toml"), "indent_space = 8");
+    write(&root.join(".typstfmt.toml"), "indent_space = 8");
+    assert_eq!(resolver.config_for(Some(&file)).unwrap().indent_space, 4);
+    assert!(resolver.take_warnings().is_empty());
+}
+
+#[test]
+fn visible_config_preferred() {
+    let root = temp_dir("visible");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join("typstfmt.toml"), "indent_space = 4");
+    write(&root.join(".typstfmt.toml"), "indent_space = 8");
+
+    let mut resolver = ConfigResolver::new();
+    let config = resolver.config_for(Some(&root.join("a.typ")));
+    assert_eq!(config.unwrap().indent_space, 4);
+    assert_eq!(resolver.take_warnings().len(), 1);
+}
+
+#[test]
+fn invalid_config() {
+    let root = temp_dir("invalid");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join("typstfmt.toml"), "indent_space = \"4\"");
+
+    let mut resolver = ConfigResolver::new();
+    let err = resolver.config_for(Some(&root.join("a.typ"))).unwrap_err();
+    assert_eq!(err.path, Some(root.join("typstfmt.toml")));
+    assert_eq!(err.key.as_deref(), Some("indent_space"));
+    assert!(
+        err.to_string()
+            .contains("typstfmt.toml:1:16: invalid `indent_space`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn overrides_are_relative_to_config_file() {
+    let root = temp_dir("overrides");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(
+        &root.join("typstfmt.toml"),
+        "max_line_length = 100\n[[overrides]]\nfiles = [\"slides/**\"]\nmax_line_length = 120\n",
+    );
+    fs::create_dir_all(root.join("slides/part")).unwrap();
+
+    let mut resolver = ConfigResolver::new();
+    let config = resolver.config_for(Some(&root.join("slides/part/a.typ")));
+    assert_eq!(config.unwrap().max_line_length, 120);
+    let config = resolver.config_for(Some(&root.join("thesis.typ")));
+    assert_eq!(config.unwrap().max_line_length, 100);
+}
+
+#[test]
+fn explicit_config_file() {
+    let root = temp_dir("explicit");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join("typstfmt.toml"), "indent_space = 4");
+    write(
+        &root.join("ci/fmt.toml"),
+        "indent_space = 8\nexclude = [\"vendor/**\"]\n",
+    );
+
+    let mut resolver = ConfigResolver::new().with_config_file(&root.join("ci/fmt.toml"));
+    let config = resolver.config_for(Some(&root.join("a.typ")));
+    assert_eq!(config.unwrap().indent_space, 8);
+    // the globs are relative to the explicit file.
+    assert!(resolver.is_excluded(&root.join("ci/vendor/a.typ")).unwrap());
+    assert!(!resolver.is_excluded(&root.join("vendor/a.typ")).unwrap());
+}
+
+#[test]
+fn no_config() {
+    let root = temp_dir("no_config");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(
+        &root.join("typstfmt.toml"),
+        "indent_space = 4\nexclude = [\"*.typ\"]",
+    );
+
+    let mut resolver = ConfigResolver::new().without_config_files();
+    let file = root.join("a.typ");
+    assert_eq!(resolver.config_for(Some(&file)).unwrap().indent_space, 2);
+    assert!(!resolver.is_excluded(&file).unwrap());
+}
+
+#[test]
+fn settings_are_applied_over_config_files() {
+    let root = temp_dir("set");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(
+        &root.join("typstfmt.toml"),
+        "indent_space = 4\n[[overrides]]\nfiles = [\"*.typ\"]\nmax_line_length = 100\n",
+    );
+
+    let mut resolver = ConfigResolver::new();
+    resolver.set("max_line_length=120").unwrap();
+    resolver.set("end_of_line = lf").unwrap();
+    resolver.set("line_wrap=false").unwrap();
+    let config = resolver.config_for(Some(&root.join("a.typ"))).unwrap();
+    assert_eq!(config.indent_space, 4);
+    assert_eq!(config.max_line_length, 120);
+    assert_eq!(config.end_of_line, EndOfLine::Lf);
+    assert!(!config.line_wrap);
+
+    let mut defaults = ConfigResolver::new()
+        .without_config_files()
+        .without_global_config();
+    defaults.set("indent_style=\"tab\"").unwrap();
+    let config = defaults.config_for(None).unwrap();
+    assert_eq!(config.indent_style, IndentStyle::Tab);
+}
+
+#[test]
+fn invalid_settings() {
+    let err = |s| ConfigResolver::new().set(s).unwrap_err();
+    assert_eq!(
+        err("max_line_length").message,
+        "expected KEY=VALUE, got \"max_line_length\""
+    );
+    let unknown = err("max_line_lenth=100");
+    assert_eq!(unknown.message, "unknown setting `max_line_lenth`");
+    assert_eq!(unknown.suggestion.as_deref(), Some("max_line_length"));
+    assert!(err("indent_space=four")
+        .message
+        .starts_with("invalid `indent_space`"));
+    assert_eq!(err("indent_space=1").key.as_deref(), Some("indent_space"));
+    assert_eq!(
+        err("exclude=[]").message,
+        "`exclude` can only be set in a configuration file"
+    );
+}
+
+#[test]
+fn explain_sources() {
+    let root = temp_dir("explain");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    let file = root.join("typstfmt.toml");
+    write(
+        &file,
+        "indent_space = 4\nmax_line_length = 100\n[[overrides]]\nfiles = [\"slides/**\"]\nmax_line_length = 120\n",
+    );
+    fs::create_dir_all(root.join("slides")).unwrap();
+
+    let mut resolver = ConfigResolver::new();
+    resolver.set("line_wrap=false").unwrap();
+    let resolved = resolver
+        .explain(Some(&root.join("slides/a.typ")))
+        .unwrap()
+        .with_pragmas("// typstfmt: verify=true\n")
+        .unwrap();
+    assert_eq!(resolved.config.max_line_length, 120);
+    assert!(resolved.config.verify);
+    let source = |key: &str| {
+        resolved
+            .settings()
+            .into_iter()
+            .find(|(name, _, _)| *name == key)
+            .map(|(_, _, source)| source.clone())
+            .unwrap()
+    };
+    assert_eq!(source("indent_space"), SettingSource::File(file.clone()));
+    assert_eq!(
+        source("max_line_length"),
+        SettingSource::Override(file.clone(), vec!["slides/**".to_string()])
+    );
+    assert_eq!(source("line_wrap"), SettingSource::Set);
+    assert_eq!(source("verify"), SettingSource::Pragma);
+    assert_eq!(source("end_of_line"), SettingSource::Default);
+
+    let other = resolver.explain(Some(&root.join("a.typ"))).unwrap();
+    assert_eq!(other.config.max_line_length, 100);
+    assert_eq!(
+        other.sources.get("max_line_length"),
+        Some(&SettingSource::File(file))
+    );
+}
+
+#[test]
+fn explain_in_config_order() {
+    let mut resolver = ConfigResolver::new()
+        .without_config_files()
+        .without_global_config();
+    let resolved = resolver.explain(None).unwrap();
+    let names = resolved
+        .settings()
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names.first(), Some(&"indent_space"));
+    assert_eq!(names.len(), Config::fields().len());
+    assert!(resolved.sources.is_empty());
+}
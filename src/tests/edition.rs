@@ -0,0 +1,55 @@
+use super::*;
+
+fn e2024() -> Config {
+    Config {
+        edition: Edition::E2024,
+        ..Default::default()
+    }
+}
+
+make_test!(function_binding_spaced, "#let f(x,y)=x+y\n", e2024());
+make_test!(
+    function_binding_not_doubly_spaced,
+    "#let f(x, y)   =   {\nx\n}\n",
+    e2024()
+);
+test_eq!(closure_arrow_untouched, "#let f = (x) => x\n", e2024());
+
+#[test]
+fn default_edition_keeps_the_old_style() {
+    assert_eq!(Config::default().edition, Edition::E2023);
+    assert_eq!(
+        format("#let f(x,y)=x+y\n", Config::default()),
+        "#let f(x, y)=x + y\n"
+    );
+}
+
+#[test]
+fn edition_as_string_or_integer() {
+    let config = Config::from_toml("edition = \"2024\"").unwrap();
+    assert_eq!(config.edition, Edition::E2024);
+    let config = Config::from_toml("edition = 2024").unwrap();
+    assert_eq!(config.edition, Edition::E2024);
+
+    let mut resolver = ConfigResolver::new()
+        .without_config_files()
+        .without_global_config();
+    resolver.set("edition=2024").unwrap();
+    assert_eq!(resolver.config_for(None).unwrap().edition, Edition::E2024);
+}
+
+#[test]
+fn unknown_edition() {
+    let err = Config::from_toml("edition = 2019").unwrap_err();
+    assert_eq!(err.key.as_deref(), Some("edition"));
+    assert!(
+        err.message
+            .contains("unknown edition `2019`, expected `2023` or `2024`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn default_config_pins_the_edition() {
+    assert!(Config::default_toml().contains("edition = \"2023\""));
+}
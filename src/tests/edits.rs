@@ -0,0 +1,49 @@
+use super::*;
+
+fn apply(s: &str, edits: &[TextEdit]) -> String {
+    let mut res = s.to_string();
+    for edit in edits.iter().rev() {
+        res.replace_range(edit.range.clone(), &edit.replacement);
+    }
+    res
+}
+
+#[test]
+fn edits_are_minimal() {
+    let input = "#let x=1\n#f(a,b)";
+    assert_eq!(
+        format_edits(input, Config::default()),
+        vec![
+            TextEdit {
+                range: 6..6,
+                replacement: " ".to_string()
+            },
+            TextEdit {
+                range: 7..7,
+                replacement: " ".to_string()
+            },
+            TextEdit {
+                range: 14..14,
+                replacement: " ".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn no_edits_when_formatted() {
+    assert!(format_edits("#let x = 1", Config::default()).is_empty());
+}
+
+#[test]
+fn edits_give_formatted() {
+    for input in [
+        "#{\n  let   x=(1,2,3)\n\n\n\n  f(x)}",
+        "#let hlinex(\n  stroke-expand: true,\n  expand: none, // some comment here\n) = ()",
+        "= été\n- a\n-   b\n$ x &= y \\ a&= b $",
+    ] {
+        let edits = format_edits(input, Config::default());
+        assert!(edits.windows(2).all(|w| w[0].range.end <= w[1].range.start));
+        similar_asserts::assert_eq!(apply(input, &edits), format(input, Config::default()));
+    }
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+#[test]
+fn try_format_refuses_and_locates_errors() {
+    let input = "#let a=1\n#f(a,   b\n\n#let x=1";
+    let Err(FormatError::Parse(errors)) = try_format(input, Config::default()) else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].range, 11..12);
+    assert_eq!((errors[0].line, errors[0].column), (2, 3));
+    assert_eq!(errors[0].message, "unclosed delimiter");
+    assert_eq!((errors[1].line, errors[1].column), (4, 1));
+}
+
+#[test]
+fn try_format_formats_valid_input() {
+    assert_eq!(
+        try_format("#let x=1", Config::default()),
+        Ok("#let x = 1".to_string())
+    );
+}
+
+#[test]
+fn broken_region_kept_verbatim() {
+    similar_asserts::assert_eq!(
+        format("#let x =\n#let y=1", Config::default()),
+        "#let x =\n#let y = 1"
+    );
+    similar_asserts::assert_eq!(
+        format("#{ let x = (1,2  }\n#f(a,b)", Config::default()),
+        "#{ let x = (1,2 }\n#f(a, b)"
+    );
+}
@@ -0,0 +1,122 @@
+use std::process::Command;
+
+use super::*;
+
+/// runs git in `dir`, without depending on the user's configuration.
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "user.name=typstfmt",
+            "-c",
+            "user.email=typstfmt@example.com",
+        ])
+        .args(["-c", "commit.gpgsign=false"])
+        .args(args)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?}");
+}
+
+/// a repository with `main.typ` committed.
+fn repo(name: &str) -> PathBuf {
+    let root = temp_dir(name);
+    git(&root, &["init", "-q"]);
+    write(
+        &root.join("main.typ"),
+        "#f(a,b)\n#g(c,d)\n#h(e,f)\n#i(g,h)\n#j(i,j)\n",
+    );
+    git(&root, &["add", "main.typ"]);
+    git(&root, &["commit", "-q", "-m", "init"]);
+    root
+}
+
+#[test]
+fn changed_since_a_revision() {
+    let root = repo("git_since");
+    let file = root.join("main.typ");
+    write(
+        &file,
+        "#f(a,b)\n#g(c,d,x)\n#h(e,f)\n#new(k,l)\n#new(m,n)\n#i(g,h)\n",
+    );
+    let changes = Changes::Since("HEAD".to_string());
+    let lines = changes.lines(&file).unwrap();
+    assert_eq!(lines, [2..=2, 4..=5]);
+
+    let content = fs::read_to_string(&file).unwrap();
+    similar_asserts::assert_eq!(
+        format_line_ranges(&content, &lines, Config::default()),
+        "#f(a,b)\n#g(c, d, x)\n#h(e,f)\n#new(k, l)\n#new(m, n)\n#i(g,h)\n"
+    );
+}
+
+#[test]
+fn unchanged_file_untouched() {
+    let root = repo("git_unchanged");
+    let file = root.join("main.typ");
+    let lines = Changes::Since("HEAD".to_string()).lines(&file).unwrap();
+    assert_eq!(lines, []);
+    let content = fs::read_to_string(&file).unwrap();
+    similar_asserts::assert_eq!(
+        format_line_ranges(&content, &lines, Config::default()),
+        content
+    );
+}
+
+#[test]
+fn untracked_file_changed_entirely() {
+    let root = repo("git_untracked");
+    let file = root.join("new.typ");
+    write(&file, "#f(a,b)\n#g(c,d)\n");
+    let lines = Changes::Since("HEAD".to_string()).lines(&file).unwrap();
+    let content = fs::read_to_string(&file).unwrap();
+    similar_asserts::assert_eq!(
+        format_line_ranges(&content, &lines, Config::default()),
+        "#f(a, b)\n#g(c, d)\n"
+    );
+    assert_eq!(Changes::Staged.lines(&file).unwrap(), []);
+}
+
+#[test]
+fn ignored_file_changed_entirely() {
+    let root = repo("git_ignored");
+    write(&root.join(".gitignore"), "build/\n");
+    let file = root.join("build/out.typ");
+    write(&file, "#f(a,b)\n");
+    let lines = Changes::Since("HEAD".to_string()).lines(&file).unwrap();
+    similar_asserts::assert_eq!(
+        format_line_ranges("#f(a,b)\n", &lines, Config::default()),
+        "#f(a, b)\n"
+    );
+}
+
+#[test]
+fn staged_lines_in_the_working_tree() {
+    let root = repo("git_staged");
+    let file = root.join("main.typ");
+    write(&file, "#f(a,b)\n#g(c,d)\n#h(e,f,x)\n#i(g,h)\n#j(i,j)\n");
+    git(&root, &["add", "main.typ"]);
+    // unstaged lines before the staged one shift it.
+    write(
+        &file,
+        "#new(k,l)\n#f(a,b)\n#g(c,d)\n#h(e,f,x)\n#i(g,h)\n#j(i,j,y)\n",
+    );
+    assert_eq!(Changes::Staged.lines(&file).unwrap(), [4..=4]);
+    assert_eq!(
+        Changes::Since("HEAD".to_string()).lines(&file).unwrap(),
+        [1..=1, 4..=4, 6..=6]
+    );
+}
+
+#[test]
+fn unknown_revision() {
+    let root = repo("git_unknown");
+    let err = Changes::Since("nope".to_string())
+        .lines(&root.join("main.typ"))
+        .unwrap_err();
+    assert!(matches!(err, GitError::Failed(_)), "{err}");
+}
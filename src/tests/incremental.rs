@@ -0,0 +1,56 @@
+use super::*;
+use crate::tests::snippets::{OFFICIAL, TABS};
+
+fn assert_same_as_format(formatter: &mut IncrementalFormatter) {
+    let expected = format(formatter.text(), Config::default());
+    similar_asserts::assert_eq!(formatter.format(), expected);
+}
+
+#[test]
+fn edits_give_same_result_as_format() {
+    let mut formatter = IncrementalFormatter::new(OFFICIAL, Config::default());
+    assert_same_as_format(&mut formatter);
+
+    let at = formatter.text().find("#figure").unwrap();
+    formatter.edit(at..at, "#let  added=(1,2)\n");
+    assert_same_as_format(&mut formatter);
+
+    let end = formatter.text().len();
+    formatter.edit(end..end, "\n\n#f(a,b)   text");
+    assert_same_as_format(&mut formatter);
+
+    formatter.edit(0..10, "");
+    assert_same_as_format(&mut formatter);
+}
+
+#[test]
+fn on_off_state_follows_edits() {
+    let mut formatter =
+        IncrementalFormatter::new("#f(a,b)\n// a comment\n#g(c,d)", Config::default());
+    assert_same_as_format(&mut formatter);
+
+    let at = formatter.text().find("a comment").unwrap();
+    formatter.edit(at..at + "a comment".len(), "typstfmt::off");
+    assert_same_as_format(&mut formatter);
+
+    formatter.replace("#f(a,b)\n// typstfmt::on\n#g(c,d)");
+    assert_same_as_format(&mut formatter);
+}
+
+#[test]
+fn tabs() {
+    let mut formatter = IncrementalFormatter::new(TABS, Config::default());
+    assert_same_as_format(&mut formatter);
+}
+
+#[test]
+fn configured_line_ending() {
+    let config = Config {
+        end_of_line: EndOfLine::Crlf,
+        ..Default::default()
+    };
+    let text = "#f(a,b)\n\n#g(c,d)\n";
+    let mut formatter = IncrementalFormatter::new(text, config);
+    assert_eq!(formatter.format(), format(text, config));
+    assert_eq!(formatter.format(), "#f(a, b)\r\n\r\n#g(c, d)\r\n");
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+fn tabs() -> Config {
+    Config {
+        indent_style: IndentStyle::Tab,
+        ..Default::default()
+    }
+}
+
+make_test!(code_block_tabs, "#{\nlet a = 1\n{\nlet b = 2\n}\n}", tabs());
+make_test!(args_tabs, "#f(\n1, // one\n2)", tabs());
+test_eq!(tab_in_string_untouched, "#let s = \"a\tb\"");
+test_eq!(tab_in_raw_untouched, "`a\tb`");
+test_eq!(tab_in_comment_untouched, "// a\tb");
+test_eq!(tab_lines_in_raw_untouched, "```\na\n\t\nb\n```");
+test_eq!(
+    tab_lines_in_raw_untouched_with_tabs,
+    "```\na\n\t\nb\n```",
+    tabs()
+);
+test_eq!(tab_lines_in_string_untouched, "#let s = \"a\n\t\nb\"");
+test_eq!(
+    tab_lines_in_string_untouched_with_tabs,
+    "#let s = \"a\n\t\nb\"",
+    tabs()
+);
+test_eq!(
+    tabs_kept_with_tab_style,
+    "#{\n\tlet s = \"a\tb\"\n}",
+    tabs()
+);
+make_test!(tabs_become_spaces, "#{\n\tlet a = 1\n}");
+
+#[test]
+fn tabs_width_counts() {
+    let input = "#{\n{\nf(aaa, bbb)\n}\n}";
+    let config = |indent_space| Config {
+        max_line_length: 20,
+        indent_space,
+        ..tabs()
+    };
+    assert_eq!(format(input, config(2)), "#{\n\t{\n\t\tf(aaa, bbb)\n\t}\n}");
+    assert_eq!(
+        format(input, config(8)),
+        "#{\n\t{\n\t\tf(\n\t\t\taaa, bbb,\n\t\t)\n\t}\n}"
+    );
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+const CRLF: &str = "#let x = (a,b)\r\n\r\n#{\r\n  let y = 1\r\n}\r\n";
+const FORMATTED_CRLF: &str = "#let x = (a, b)\r\n\r\n#{\r\n  let y = 1\r\n}\r\n";
+
+fn with_eol(end_of_line: EndOfLine) -> Config {
+    Config {
+        end_of_line,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn crlf_is_kept() {
+    assert_eq!(format(CRLF, Config::default()), FORMATTED_CRLF);
+    assert_eq!(format(FORMATTED_CRLF, Config::default()), FORMATTED_CRLF);
+}
+
+#[test]
+fn dominant_line_ending_wins() {
+    assert_eq!(
+        format("a\r\n\r\nb\r\n\r\nc\n\nd", Config::default()),
+        "a\r\n\r\nb\r\n\r\nc\r\n\r\nd"
+    );
+    assert_eq!(
+        format("a\r\n\r\nb\n\nc\n\nd", Config::default()),
+        "a\n\nb\n\nc\n\nd"
+    );
+}
+
+#[test]
+fn configured_line_ending() {
+    assert_eq!(
+        format(CRLF, with_eol(EndOfLine::Lf)),
+        FORMATTED_CRLF.replace("\r\n", "\n")
+    );
+    assert_eq!(
+        format(&CRLF.replace("\r\n", "\n"), with_eol(EndOfLine::Crlf)),
+        FORMATTED_CRLF
+    );
+}
+
+#[test]
+fn comment_line_endings_follow() {
+    let comment = "/* a\r\nb */\r\n";
+    assert_eq!(format(comment, with_eol(EndOfLine::Auto)), comment);
+    assert_eq!(format(comment, with_eol(EndOfLine::Lf)), "/* a\nb */\n");
+}
+
+#[test]
+fn verify_ignores_line_endings() {
+    let config = Config {
+        verify: true,
+        end_of_line: EndOfLine::Lf,
+        ..Default::default()
+    };
+    assert_eq!(
+        try_format(CRLF, config).unwrap(),
+        FORMATTED_CRLF.replace("\r\n", "\n")
+    );
+}
+
+#[test]
+fn range_keeps_crlf() {
+    let s = "#f(a,b)\r\n#g(c,d)\r\n";
+    assert_eq!(
+        format_lines(s, 2..=2, Config::default()),
+        "#f(a,b)\r\n#g(c, d)\r\n"
+    );
+}
@@ -0,0 +1,171 @@
+use super::*;
+use serde_json::{json, Value};
+
+/// frames a message with its `Content-Length` header.
+fn frame(content: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{content}", content.len())
+}
+
+/// runs the server on a script of messages and returns its responses.
+fn run(script: &[Value]) -> Vec<Value> {
+    let input = script.iter().map(|message| frame(&message.to_string()));
+    run_raw(&input.collect::<String>())
+}
+
+/// runs the server on raw input and returns its responses.
+fn run_raw(input: &str) -> Vec<Value> {
+    let mut output = vec![];
+    crate::lsp::serve(input.as_bytes(), &mut output, |_| Config::default()).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    output
+        .split("Content-Length: ")
+        .filter(|s| !s.is_empty())
+        .map(|s| serde_json::from_str(s.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+
+fn open(text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": {
+            "uri": "file:///doc.typ", "languageId": "typst", "version": 1, "text": text,
+        }},
+    })
+}
+
+fn request(id: i64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+#[test]
+fn session() {
+    let responses = run(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        open("#f(a,b)\n#g(c,d)"),
+        request(
+            2,
+            "textDocument/formatting",
+            json!({ "textDocument": { "uri": "file:///doc.typ" }, "options": {} }),
+        ),
+        request(
+            3,
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": { "uri": "file:///doc.typ" },
+                "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 7 } },
+                "options": {},
+            }),
+        ),
+        request(4, "shutdown", Value::Null),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+        request(5, "shutdown", Value::Null),
+    ]);
+
+    assert_eq!(responses.len(), 4);
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["documentFormattingProvider"],
+        true
+    );
+    let edit = |line: u64| {
+        json!({
+            "range": {
+                "start": { "line": line, "character": 5 },
+                "end": { "line": line, "character": 5 },
+            },
+            "newText": " ",
+        })
+    };
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(responses[1]["result"], json!([edit(0), edit(1)]));
+    assert_eq!(responses[2]["result"], json!([edit(1)]));
+    assert_eq!(responses[3]["result"], Value::Null);
+}
+
+#[test]
+fn on_type_formats_finished_line() {
+    let responses = run(&[
+        open("#let x=1\n#let y=(1,2)\n"),
+        request(
+            1,
+            "textDocument/onTypeFormatting",
+            json!({
+                "textDocument": { "uri": "file:///doc.typ" },
+                "position": { "line": 2, "character": 0 },
+                "ch": "\n",
+                "options": {},
+            }),
+        ),
+    ]);
+    assert_eq!(
+        responses[0]["result"],
+        json!([
+            { "range": { "start": { "line": 1, "character": 6 }, "end": { "line": 1, "character": 6 } }, "newText": " " },
+            { "range": { "start": { "line": 1, "character": 7 }, "end": { "line": 1, "character": 7 } }, "newText": " " },
+            { "range": { "start": { "line": 1, "character": 10 }, "end": { "line": 1, "character": 10 } }, "newText": " " },
+        ])
+    );
+}
+
+#[test]
+fn unknown_method() {
+    let responses = run(&[request(1, "textDocument/hover", json!({}))]);
+    assert_eq!(responses[0]["error"]["code"], -32601);
+}
+
+#[test]
+fn utf16_positions() {
+    let responses = run(&[
+        open("#let 𝔸=f(a,b)"),
+        request(
+            1,
+            "textDocument/formatting",
+            json!({ "textDocument": { "uri": "file:///doc.typ" }, "options": {} }),
+        ),
+    ]);
+    assert_eq!(
+        responses[0]["result"][0]["range"]["start"],
+        json!({ "line": 0, "character": 7 })
+    );
+}
+
+#[test]
+fn invalid_json_keeps_serving() {
+    let shutdown = request(2, "shutdown", Value::Null).to_string();
+    let responses = run_raw(&format!("{}{}", frame("{\"id\": 1,"), frame(&shutdown)));
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], Value::Null);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(responses[1]["result"], Value::Null);
+}
+
+#[test]
+fn missing_content_length_keeps_serving() {
+    let shutdown = request(2, "shutdown", Value::Null).to_string();
+    let responses = run_raw(&format!("Content-Type: x\r\n\r\n{}", frame(&shutdown)));
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(responses[1]["id"], 2);
+}
+
+#[test]
+fn responses_of_the_client_ignored() {
+    let responses = run(&[
+        json!({ "jsonrpc": "2.0", "id": 7, "result": null }),
+        json!({ "jsonrpc": "2.0", "id": 8, "error": { "code": -1, "message": "no" } }),
+        request(2, "shutdown", Value::Null),
+    ]);
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0]["id"], 2);
+}
+
+#[test]
+fn truncated_input_ends_cleanly() {
+    assert_eq!(
+        run_raw("Content-Length: 100\r\n\r\n{\"id\""),
+        Vec::<Value>::new()
+    );
+}
@@ -1,3 +1,6 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use super::*;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
@@ -34,7 +37,7 @@ fn init() {
 
 /// This makes :
 /// - A snapshot test where you're prompted to say if you're snippet is nicely formatted.
-/// (see README.md)
+///   (see README.md)
 /// - A double format test (if an input is formatted twice it should give the same result)
 /// - An AST test (if an input is formatted, the output AST should be the same as the input).
 ///
@@ -111,60 +114,21 @@ macro_rules! test_eq {
     };
 }
 
-// allowing modifying trailing comma's, text in markup, space everywhere
-// todo, check adding all text from one tree and another equal the same text.
-fn tree_are_equal(node: &LinkedNode, other_node: &LinkedNode) -> bool {
-    let should_ignore = |x: &LinkedNode| [Space, Parbreak, Comma, Text].contains(&x.kind());
-
-    let node_kind = node.kind();
-    let other_kind = other_node.kind();
-    if node_kind != other_kind {
-        debug!("kind differs! {:?}-{:?}", node_kind, other_kind);
-        return false;
-    }
-
-    if (node.text() != other_node.text()) && !should_ignore(node) {
-        debug!(
-            "kind ok {:?}\ntext differ:{:?}-{:?}",
-            node.kind(),
-            node.text(),
-            other_node.text()
-        );
-        return false;
-    }
+/// an empty directory in the temporary directory, unique to a test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("typstfmt-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
 
-    let fchildren = node.children().filter(|x| !should_ignore(x)).collect_vec();
-    let fchildren_oth = other_node
-        .children()
-        .filter(|x| !should_ignore(x))
-        .collect_vec();
-    if fchildren.len() != fchildren_oth.len() {
-        debug!(
-            "children count differ! {:?}\n{:?}",
-            fchildren, fchildren_oth
-        );
-        return false;
-    }
-    if node
-        .children()
-        .filter(|x| !should_ignore(x))
-        .zip(other_node.children().filter(|x| !should_ignore(x)))
-        .any(|(c, oth)| !tree_are_equal(&c, &oth))
-    {
-        return false;
-    }
-    true
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
 }
 
-#[instrument(skip_all)]
 fn parses_the_same(s: &str, oth: &str) -> bool {
-    let parse1 = parse(s);
-    let lkn = LinkedNode::new(&parse1);
-    let parse2 = parse(oth);
-    let lkn_oth = LinkedNode::new(&parse2);
-    debug!("{:?}", parse1);
-    debug!("{:?}", parse2);
-    tree_are_equal(&lkn, &lkn_oth)
+    crate::verify::difference(s, oth).is_none()
 }
 
 #[test]
@@ -176,8 +140,26 @@ fn comma_gets_ignored_in_comparison() {
 mod code_block;
 mod comments;
 mod conditionals;
+mod config;
+mod diff;
+mod discovery;
+mod edition;
+mod edits;
+mod errors;
+mod git;
+mod incremental;
+mod indent_style;
+mod line_endings;
 mod lists;
+mod lsp;
 mod markup;
 mod math;
+mod overrides;
 mod params;
+mod pragma;
+mod range;
+mod report;
+mod schema;
 mod snippets;
+mod verify;
+mod walk;
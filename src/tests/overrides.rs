@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use super::*;
+
+const CONFIG: &str = r#"
+max_line_length = 100
+indent_space = 4
+
+[[overrides]]
+files = ["slides/**", "*.slides.typ"]
+max_line_length = 120
+line_wrap = false
+
+[[overrides]]
+files = ["slides/appendix/**"]
+max_line_length = 60
+"#;
+
+#[test]
+fn no_override_matches() {
+    let file = ConfigFile::from_toml(CONFIG).unwrap();
+    let config = file.config_for(Path::new("thesis/main.typ"));
+    assert_eq!(config.max_line_length, 100);
+    assert!(config.line_wrap);
+    assert_eq!(config.indent_space, 4);
+}
+
+#[test]
+fn matching_overrides_merge_in_order() {
+    let file = ConfigFile::from_toml(CONFIG).unwrap();
+    let config = file.config_for(Path::new("slides/intro.typ"));
+    assert_eq!(config.max_line_length, 120);
+    assert!(!config.line_wrap);
+    assert_eq!(config.indent_space, 4);
+
+    let config = file.config_for(Path::new("slides/appendix/a.typ"));
+    assert_eq!(config.max_line_length, 60);
+    assert!(!config.line_wrap);
+
+    let config = file.config_for(Path::new("talk.slides.typ"));
+    assert_eq!(config.max_line_length, 120);
+}
+
+#[test]
+fn from_toml_ignores_overrides() {
+    let config = Config::from_toml(CONFIG).unwrap();
+    assert_eq!(config.max_line_length, 100);
+    assert!(config.line_wrap);
+}
+
+#[test]
+fn invalid_overrides() {
+    let err = |s| ConfigFile::from_toml(s).unwrap_err();
+    assert_eq!(
+        err("[[overrides]]\nmax_line_length = 120").message,
+        "missing field `files` in [[overrides]]"
+    );
+    let unknown = err("[[overrides]]\nfiles = [\"a/**\"]\nmax_line = 120");
+    assert_eq!(unknown.message, "unknown setting `max_line`");
+    assert_eq!(unknown.line_column(), Some((3, 1)));
+    assert!(err("[[overrides]]\nfiles = [\"a/[\"]")
+        .message
+        .starts_with("invalid glob"));
+    assert_eq!(
+        err("overrides = 1").message,
+        "overrides must be an array of tables: [[overrides]]"
+    );
+}
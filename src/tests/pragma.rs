@@ -0,0 +1,85 @@
+use super::*;
+
+make_test!(
+    pragma_max_line_length,
+    "// typstfmt: max_line_length=20\n#f(argument, argument, argument)\n",
+);
+make_test!(
+    pragma_indent,
+    "// some comment\n\n// typstfmt: indent_space=4, indent_style=space,\n#{\nlet a = 1\n}\n",
+);
+test_eq!(
+    pragma_line_wrap,
+    "// typstfmt: line_wrap=false, max_line_length=10\nSome long text that is not wrapped.\n",
+);
+
+#[test]
+fn pragmas_are_read_at_the_top() {
+    let config = Config::default()
+        .with_pragmas(
+            "// typstfmt: max_line_length = 120\n// typstfmt: line_wrap=false, end_of_line=crlf\n",
+        )
+        .unwrap();
+    assert_eq!(config.max_line_length, 120);
+    assert!(!config.line_wrap);
+    assert_eq!(config.end_of_line, EndOfLine::Crlf);
+
+    let after_content = Config::default()
+        .with_pragmas("#f()\n// typstfmt: max_line_length=120\n")
+        .unwrap();
+    assert_eq!(after_content, Config::default());
+}
+
+#[test]
+fn pragmas_win_over_the_config() {
+    let config = Config {
+        max_line_length: 40,
+        indent_space: 4,
+        ..Default::default()
+    };
+    let config = config
+        .with_pragmas("// typstfmt: max_line_length=120\n")
+        .unwrap();
+    assert_eq!(config.max_line_length, 120);
+    assert_eq!(config.indent_space, 4);
+}
+
+#[test]
+fn on_off_are_not_settings() {
+    let config = Config::default()
+        .with_pragmas("// typstfmt::off\n// typstfmt::on\n")
+        .unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn invalid_pragma_points_at_the_setting() {
+    let input = "// title\n// typstfmt: line_wrap=false,  max_line_lenght=120\n#f()\n";
+    let err = Config::default().with_pragmas(input).unwrap_err();
+    assert_eq!(
+        err.message,
+        "unknown setting `max_line_lenght` in a typstfmt pragma"
+    );
+    assert_eq!(err.suggestion.as_deref(), Some("max_line_length"));
+    assert_eq!(err.line_column(), Some((2, 32)));
+    assert_eq!(&input[err.range.unwrap()], "max_line_lenght=120");
+}
+
+#[test]
+fn invalid_pragma_is_ignored_when_formatting() {
+    let input = "// typstfmt: indent_space=1\n#f(a,b)\n";
+    assert_eq!(
+        format(input, Config::default()),
+        "// typstfmt: indent_space=1\n#f(a, b)\n"
+    );
+}
+
+#[test]
+fn incremental_follows_pragma_changes() {
+    let mut formatter = IncrementalFormatter::new("#f(argument, argument)\n", Config::default());
+    assert_eq!(formatter.format(), "#f(argument, argument)\n");
+    formatter.edit(0..0, "// typstfmt: max_line_length=10\n");
+    let expected = format(formatter.text(), Config::default());
+    assert_eq!(formatter.format(), expected);
+    assert_ne!(expected, formatter.text());
+}
@@ -0,0 +1,131 @@
+use super::*;
+
+#[test]
+fn only_selected_lines() {
+    similar_asserts::assert_eq!(
+        format_lines("#f(a,b)\n#g(c,d)\n#h(e,f)", 2..=2, Config::default()),
+        "#f(a,b)\n#g(c, d)\n#h(e,f)"
+    );
+}
+
+#[test]
+fn keeps_indentation_of_enclosing_block() {
+    let input = "#{\n  let x = (1,2)\n  let y  =  {\n  (3,4)\n  }\n}";
+    similar_asserts::assert_eq!(
+        format_lines(input, 3..=5, Config::default()),
+        "#{\n  let x = (1,2)\n  let y = {\n    (3, 4)\n  }\n}"
+    );
+}
+
+#[test]
+fn smallest_enclosing_node() {
+    let input = "#f(a,b) #g(c,d)";
+    let cursor = input.find('c').unwrap();
+    similar_asserts::assert_eq!(
+        format_range(input, cursor..cursor, Config::default()),
+        "#f(a,b) #g(c, d)"
+    );
+}
+
+#[test]
+fn whole_range_formats_everything() {
+    let input = "#let x=1\n#f(a,b)";
+    similar_asserts::assert_eq!(
+        format_range(input, 0..input.len(), Config::default()),
+        format(input, Config::default())
+    );
+}
+
+#[test]
+fn respects_off() {
+    let input = "// typstfmt::off\n#f(a,b)\n// typstfmt::on\n#g(c,d)";
+    similar_asserts::assert_eq!(format_lines(input, 2..=2, Config::default()), input);
+    similar_asserts::assert_eq!(
+        format_lines(input, 4..=4, Config::default()),
+        "// typstfmt::off\n#f(a,b)\n// typstfmt::on\n#g(c, d)"
+    );
+}
+
+#[test]
+fn several_line_ranges() {
+    similar_asserts::assert_eq!(
+        format_line_ranges(
+            "#f(a,b)\n#g(c,d)\n#h(e,f)\n#i(g,h)",
+            &[1..=1, 3..=3],
+            Config::default()
+        ),
+        "#f(a, b)\n#g(c,d)\n#h(e, f)\n#i(g,h)"
+    );
+}
+
+#[test]
+fn nested_ranges_formatted_once() {
+    let input = "#{\n  let x = (1,2)\n  let y  =  {\n  (3,4)\n  }\n}";
+    similar_asserts::assert_eq!(
+        format_line_ranges(input, &[3..=5, 4..=4, 3..=5], Config::default()),
+        format_lines(input, 3..=5, Config::default())
+    );
+}
+
+#[test]
+fn no_ranges_untouched() {
+    let input = "#f(a,b)\n";
+    similar_asserts::assert_eq!(format_ranges(input, &[], Config::default()), input);
+}
+
+#[test]
+fn errors_within_the_lines_refused() {
+    let input = "#f(a,b)\n#g(c,\n#h(e,f)";
+    let Err(FormatError::Parse(errors)) =
+        try_format_line_ranges(input, &[2..=2], Config::default())
+    else {
+        panic!("expected a parse error");
+    };
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn errors_outside_the_lines_tolerated() {
+    let input = "#f(a,b)\n#g(c,d)\n#h(e,";
+    similar_asserts::assert_eq!(
+        try_format_line_ranges(input, &[1..=1], Config::default()).unwrap(),
+        "#f(a, b)\n#g(c,d)\n#h(e,"
+    );
+}
+
+#[test]
+fn verification_failure_within_the_lines() {
+    let input = "#{\n  if a { b } // c\n  else { d }\n}";
+    let config = Config {
+        verify: true,
+        ..Default::default()
+    };
+    assert!(matches!(
+        try_format_line_ranges(input, &[1..=4], config),
+        Err(FormatError::Verification(_))
+    ));
+    similar_asserts::assert_eq!(format_line_ranges(input, &[1..=4], config), input);
+}
+
+#[test]
+fn crlf_formatted_like_lf() {
+    let lf = "a  b\n\n\n\nc\n#f(a,b)\n";
+    let crlf = &lf.replace('\n', "\r\n");
+    similar_asserts::assert_eq!(
+        format_range(crlf, 0..crlf.len(), Config::default()),
+        format(crlf, Config::default())
+    );
+    similar_asserts::assert_eq!(
+        format_lines(crlf, 1..=6, Config::default()),
+        format_lines(lf, 1..=6, Config::default()).replace('\n', "\r\n")
+    );
+}
+
+#[test]
+fn mixed_line_endings_outside_the_range_kept() {
+    let s = "#f(a,b)\r\n#g(c,d)\n#h(e,f)\r\n";
+    similar_asserts::assert_eq!(
+        format_lines(s, 3..=3, Config::default()),
+        "#f(a,b)\r\n#g(c,d)\n#h(e, f)\r\n"
+    );
+}
@@ -0,0 +1,103 @@
+use super::*;
+
+fn reports() -> Vec<FileReport> {
+    vec![
+        FileReport::new("a.typ".to_string(), "#f(a, b)\n", "#f(a, b)\n"),
+        FileReport::new(
+            "dir/b.typ".to_string(),
+            "#f(a,b)\nsame\n#g(c,d)\n#h(e,f)\n",
+            "#f(a, b)\nsame\n#g(c, d)\n#h(e, f)\n",
+        ),
+        FileReport::error("c,d.typ".to_string(), "could not parse\ninput".to_string()),
+    ]
+}
+
+#[test]
+fn changed_lines_are_merged() {
+    assert_eq!(changed_lines("a\nb\n", "a\nb\n"), vec![]);
+    assert_eq!(changed_lines("a\nb\nc\n", "a\nB\nc\n"), vec![2..=2]);
+    assert_eq!(
+        changed_lines("a\nb\nc\nd\ne\n", "A\nb\nC\nD\ne\n"),
+        vec![1..=1, 3..=4]
+    );
+    assert_eq!(changed_lines("a\nb\nc\n", "a\nc\n"), vec![2..=2]);
+}
+
+#[test]
+fn inserted_lines_are_on_the_line_before() {
+    assert_eq!(changed_lines("a\nb\n", "a\nnew\nb\n"), vec![1..=1]);
+    assert_eq!(changed_lines("a\n", "new\na\n"), vec![1..=1]);
+    assert_eq!(changed_lines("a", "a\n"), vec![1..=1]);
+}
+
+#[test]
+fn report_of_a_formatted_file() {
+    let reports = reports();
+    assert!(reports[0].is_formatted());
+    assert!(!reports[1].is_formatted());
+    assert_eq!(reports[1].changes, vec![1..=1, 3..=4]);
+    assert!(!reports[2].is_formatted());
+}
+
+#[test]
+fn json() {
+    let json: serde_json::Value =
+        serde_json::from_str(&ReportFormat::Json.render(&reports())).unwrap();
+    assert_eq!(json["files"][0]["formatted"], true);
+    assert_eq!(
+        json["files"][1]["changes"],
+        serde_json::json!([
+            { "start_line": 1, "end_line": 1 },
+            { "start_line": 3, "end_line": 4 },
+        ])
+    );
+    assert_eq!(json["files"][2]["error"], "could not parse\ninput");
+}
+
+#[test]
+fn sarif() {
+    let sarif: serde_json::Value =
+        serde_json::from_str(&ReportFormat::Sarif.render(&reports())).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    let location = &results[1]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "dir/b.typ");
+    assert_eq!(location["region"]["startLine"], 3);
+    assert_eq!(location["region"]["endLine"], 4);
+    assert_eq!(results[1]["message"]["text"], "lines 3-4 need formatting");
+    assert_eq!(results[2]["level"], "error");
+}
+
+#[test]
+fn checkstyle() {
+    let xml = ReportFormat::Checkstyle.render(&reports());
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle"));
+    assert!(
+        xml.contains("  <file name=\"a.typ\">\n  </file>\n"),
+        "{xml}"
+    );
+    assert!(xml.contains(
+        "<error line=\"3\" severity=\"warning\" message=\"lines 3-4 need formatting\" source=\"typstfmt\"/>"
+    ));
+    assert!(
+        xml.contains("message=\"could not parse&#10;input\""),
+        "{xml}"
+    );
+}
+
+#[test]
+fn github() {
+    similar_asserts::assert_eq!(
+        ReportFormat::Github.render(&reports()),
+        "::warning file=dir/b.typ,line=1,endLine=1,title=typstfmt::line 1 needs formatting\n\
+         ::warning file=dir/b.typ,line=3,endLine=4,title=typstfmt::lines 3-4 need formatting\n\
+         ::error file=c%2Cd.typ,title=typstfmt::could not parse%0Ainput\n"
+    );
+}
+
+#[test]
+fn parse_report_format() {
+    assert_eq!("sarif".parse(), Ok(ReportFormat::Sarif));
+    assert!("xml".parse::<ReportFormat>().is_err());
+}
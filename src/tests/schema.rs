@@ -0,0 +1,83 @@
+use super::*;
+
+#[test]
+fn every_setting_is_in_the_schema() {
+    let schema = config_schema();
+    let properties = schema["properties"].as_object().unwrap();
+    let overrides = schema["properties"]["overrides"]["items"]["properties"]
+        .as_object()
+        .unwrap();
+    let defaults = serde_json::to_value(Config::default()).unwrap();
+    for field in Config::fields() {
+        let setting = &properties[&field];
+        let description = setting["description"].as_str().unwrap_or_default();
+        assert!(!description.is_empty(), "{field} has no description");
+        assert_eq!(setting["default"], defaults[&field], "{field}");
+        // a setting written in several ways has a type for each.
+        let types = match setting["oneOf"].as_array() {
+            Some(types) => types.iter().map(|one| &one["type"]).collect(),
+            None => vec![&setting["type"]],
+        };
+        assert!(types.iter().all(|t| t.is_string()), "{field} has no type");
+        assert_eq!(overrides[&field]["type"], setting["type"], "{field}");
+        assert_eq!(overrides[&field]["oneOf"], setting["oneOf"], "{field}");
+    }
+    assert_eq!(properties.len(), Config::fields().len() + 2);
+    assert!(properties.contains_key("exclude"));
+    assert!(overrides.contains_key("files"));
+}
+
+#[test]
+fn descriptions_come_from_doc_comments() {
+    let schema = config_schema();
+    assert_eq!(
+        schema["properties"]["indent_space"]["description"],
+        "The width of an indentation level, with tabs, the width of a tab."
+    );
+    assert_eq!(
+        schema["properties"]["verify"]["description"],
+        "If enabled, the output is parsed again and compared to the input,\n\
+         if the formatting would change the meaning of the document it is left untouched."
+    );
+    assert_eq!(
+        schema["properties"]["line_wrap"]["description"],
+        "If enabled, text in markup is wrapped at `max_line_length`."
+    );
+}
+
+#[test]
+fn enum_values_are_accepted() {
+    let schema = config_schema();
+    for field in ["indent_style", "end_of_line"] {
+        let values = schema["properties"][field]["enum"].as_array().unwrap();
+        assert!(values.len() > 1);
+        for value in values {
+            let toml = format!("{field} = {value}");
+            assert!(Config::from_toml(&toml).is_ok(), "{toml}");
+        }
+    }
+    assert_eq!(
+        schema["properties"]["end_of_line"]["enum"],
+        serde_json::json!(["auto", "lf", "crlf"])
+    );
+}
+
+#[test]
+fn edition_as_integer_or_string() {
+    let schema = config_schema();
+    let edition = &schema["properties"]["edition"];
+    assert_eq!(
+        edition["oneOf"],
+        serde_json::json!([
+            { "type": "integer", "enum": [2023, 2024] },
+            { "type": "string", "enum": ["2023", "2024"] },
+        ])
+    );
+    assert_eq!(edition["default"], "2023");
+    for one in edition["oneOf"].as_array().unwrap() {
+        for value in one["enum"].as_array().unwrap() {
+            let toml = format!("edition = {value}");
+            assert!(Config::from_toml(&toml).is_ok(), "{toml}");
+        }
+    }
+}
@@ -5,6 +5,10 @@ test_eq!(let_stmt_period_terminated, "#let ident = variable;");
 make_test!(let_stmt_no_spacing, "#let ident=variable");
 make_test!(ten_adds, &format!("#{{{}1}}", "1+".repeat(10)));
 make_test!(thirty_adds, &format!("#{{{}1}}", "1+".repeat(30)));
+make_test!(
+    thirty_adds_parenthesized,
+    &format!("#let x = ({}1)", "100+".repeat(30))
+);
 test_eq!(not_in, "#let page_turned = page not in header_pages");
 make_test!(
     while_loop,
@@ -76,7 +80,7 @@ test_eq!(
 //    $step(&>= ceil(phi.alt (n+1)) / (n+1) >= phi.alt. )$
 // vs $step(&>= ceil(phi.alt (n+1)) / (n+1) >= phi.alt.)$
 
-const OFFICIAL: &str = r#"Glaciers as the one shown in
+pub(super) const OFFICIAL: &str = r#"Glaciers as the one shown in
 @glaciers will cease to exist if
 we don't take action soon!
 
@@ -98,7 +102,7 @@ fn main() {
  }
 }```"#;
 
-const TABS: &str = r#"
+pub(super) const TABS: &str = r#"
 #{
 	{
     v(0pt)
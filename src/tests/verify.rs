@@ -0,0 +1,50 @@
+use super::*;
+
+const COMMENT_BEFORE_ELSE: &str = "#{\n  if a { b } // c\n  else { d }\n}";
+
+#[test]
+fn unverified_output_changes_meaning() {
+    assert!(!tests::parses_the_same(
+        COMMENT_BEFORE_ELSE,
+        &format(COMMENT_BEFORE_ELSE, Config::default())
+    ));
+}
+
+#[test]
+fn verify_keeps_original() {
+    let config = Config {
+        verify: true,
+        ..Default::default()
+    };
+    assert_eq!(format(COMMENT_BEFORE_ELSE, config), COMMENT_BEFORE_ELSE);
+    let Err(FormatError::Verification(diagnostic)) = try_format(COMMENT_BEFORE_ELSE, config) else {
+        panic!("expected the verification to fail");
+    };
+    assert!(diagnostic.contains("1:2 in the input"), "{diagnostic}");
+}
+
+#[test]
+fn verify_accepts_good_formatting() {
+    let config = Config {
+        verify: true,
+        ..Default::default()
+    };
+    assert_eq!(format("#f(a,b,)", config), "#f(a, b)");
+}
+
+#[test]
+fn dropped_word_fails() {
+    let diagnostic = crate::verify::difference("Hello big world", "Hello world").unwrap();
+    assert!(diagnostic.starts_with("text differs"), "{diagnostic}");
+    assert!(!tests::parses_the_same("*a b* c", "*a* c"));
+    assert!(!tests::parses_the_same("a #f() b", "a b #f()"));
+}
+
+#[test]
+fn rewrapped_text_passes() {
+    assert!(tests::parses_the_same(
+        "Hello big\nwide world",
+        "Hello\nbig wide world\n"
+    ));
+    assert!(tests::parses_the_same("a  #f()  b", "a #f() b"));
+}
@@ -0,0 +1,115 @@
+use super::*;
+
+fn relative(root: &Path, files: Vec<PathBuf>) -> Vec<String> {
+    files
+        .iter()
+        .map(|f| {
+            f.strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect()
+}
+
+#[test]
+fn finds_typst_files_sorted() {
+    let root = temp_dir("walk_sorted");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    for file in [
+        "b.typ",
+        "a.typ",
+        "notes.md",
+        "chapters/z.typ",
+        "chapters/1/a.typ",
+    ] {
+        write(&root.join(file), "");
+    }
+    let files = typst_files(&root, &mut ConfigResolver::new()).unwrap();
+    assert_eq!(
+        relative(&root, files),
+        ["a.typ", "b.typ", "chapters/1/a.typ", "chapters/z.typ"]
+    );
+}
+
+#[test]
+fn ignore_files_and_exclude() {
+    let root = temp_dir("walk_ignore");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(
+        &root.join(".gitignore"),
+        "# build output\nbuild/\n*.gen.typ\n",
+    );
+    write(&root.join(".typstfmtignore"), "/vendor\n!keep.gen.typ\n");
+    write(&root.join("typstfmt.toml"), "exclude = [\"slides/old/**\"]");
+    write(&root.join("sub/.gitignore"), "draft.typ\n");
+    for file in [
+        "main.typ",
+        "build/out.typ",
+        "a.gen.typ",
+        "keep.gen.typ",
+        "vendor/lib.typ",
+        "sub/vendor/lib.typ",
+        "sub/draft.typ",
+        "draft.typ",
+        "slides/old/a.typ",
+        "slides/new.typ",
+        ".git/hooks/x.typ",
+    ] {
+        write(&root.join(file), "");
+    }
+    let files = typst_files(&root, &mut ConfigResolver::new()).unwrap();
+    assert_eq!(
+        relative(&root, files),
+        [
+            "draft.typ",
+            "keep.gen.typ",
+            "main.typ",
+            "slides/new.typ",
+            "sub/vendor/lib.typ"
+        ]
+    );
+}
+
+#[test]
+fn parent_ignore_files_apply() {
+    let root = temp_dir("walk_parent");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(&root.join(".gitignore"), "generated/\n");
+    write(&root.join("book/generated/a.typ"), "");
+    write(&root.join("book/a.typ"), "");
+    let files = typst_files(&root.join("book"), &mut ConfigResolver::new()).unwrap();
+    assert_eq!(relative(&root, files), ["book/a.typ"]);
+}
+
+#[test]
+fn skipped_like_the_search() {
+    let root = temp_dir("walk_skipped");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write(
+        &root.join(".gitignore"),
+        "build/\n*.gen.typ\n!keep.gen.typ\n",
+    );
+    write(&root.join("typstfmt.toml"), "exclude = [\"slides/old/**\"]");
+    write(&root.join("sub/.typstfmtignore"), "draft.typ\n");
+    let mut resolver = ConfigResolver::new();
+    // the files don't have to exist.
+    for (file, skipped) in [
+        ("main.typ", false),
+        ("build/out.typ", true),
+        ("build/deep/out.typ", true),
+        ("a.gen.typ", true),
+        ("keep.gen.typ", false),
+        ("slides/old/a.typ", true),
+        ("slides/new.typ", false),
+        ("sub/draft.typ", true),
+        ("draft.typ", false),
+        (".git/x.typ", true),
+    ] {
+        assert_eq!(
+            is_skipped(&root.join(file), &mut resolver).unwrap(),
+            skipped,
+            "{file}"
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use super::*;
+use std::borrow::Cow;
+
 use unicode_width::UnicodeWidthStr as _;
 
+use super::*;
+
 /// like next sibling but doesn't skip trivia.
 pub(crate) fn next_sibling_or_trivia<'a>(node: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
     node.parent()?.children().nth(node.index() + 1)
@@ -140,14 +143,74 @@ pub(crate) fn prev_is_ignoring(node: &LinkedNode, is: SyntaxKind, ignoring: &[Sy
     n.is_some_and(|n| is == n.kind())
 }
 
-pub(crate) fn max_line_length(s: &str) -> usize {
-    s.lines().map(|l| l.width()).max().unwrap_or(0)
+/// line and column (in chars) of an offset, both starting at 1.
+pub(crate) fn line_column(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// replaces `\r\n` line endings by `\n`.
+pub(crate) fn normalize_line_endings(s: &str) -> Cow<'_, str> {
+    if s.contains("\r\n") {
+        Cow::Owned(s.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// uses `eol` as the line ending of every line.
+pub(crate) fn with_line_endings(s: &str, eol: &str) -> String {
+    let s = normalize_line_endings(s);
+    if eol == "\n" {
+        s.into_owned()
+    } else {
+        s.replace('\n', eol)
+    }
+}
+
+/// the width of a string, tabs being `tab_width` wide.
+pub(crate) fn str_width(s: &str, tab_width: usize) -> usize {
+    // tabs are control characters, they have no width.
+    s.width() + s.matches('\t').count() * tab_width
 }
 
-pub(crate) fn last_line_length(s: &str) -> usize {
-    s.split('\n').last().unwrap_or("").width()
+/// removes spaces at the end of lines.
+///
+/// Tabs are left alone, the printer never ends a line with indentation so they can only come
+/// from the input, like raw blocks and strings.
+pub(crate) fn trim_trailing_spaces(s: &str) -> String {
+    regex::Regex::new("( )+\n")
+        .unwrap()
+        .replace_all(s, "\n")
+        .to_string()
 }
 
-pub(crate) fn first_line_length(s: &str) -> usize {
-    s.split('\n').next().unwrap_or("").width()
+/// the candidate closest to `s`, if it's close enough to be a typo.
+pub(crate) fn closest<'a>(s: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (edit_distance(s, c), c))
+        .filter(|(distance, c)| *distance <= 3.min(c.len() / 2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c.as_str())
+}
+
+/// the number of chars to insert, delete or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect_vec();
+    let mut row = (0..=b.len()).collect_vec();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
 }
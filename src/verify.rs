@@ -0,0 +1,128 @@
+use super::*;
+
+/// Returns `formatted` unless [Config::verify] is enabled and it doesn't parse like `original`,
+/// in which case the `original` is returned.
+pub(crate) fn checked(original: &str, formatted: String, config: Config) -> String {
+    if config.verify {
+        if let Some(diagnostic) = difference(original, &formatted) {
+            warn!("formatting would change the meaning of the input, leaving it untouched: {diagnostic}");
+            return original.to_string();
+        }
+    }
+    formatted
+}
+
+/// Checks that `formatted` parses to the same tree as `original`,
+/// returns a description of the first difference if it doesn't.
+///
+/// Spaces, parbreaks, commas and line endings are allowed to change, text in markup may be
+/// rewrapped but its words must stay the same.
+#[instrument(skip_all)]
+pub(crate) fn difference(original: &str, formatted: &str) -> Option<String> {
+    let original = &utils::normalize_line_endings(original);
+    let formatted = &utils::normalize_line_endings(formatted);
+    let parse1 = parse(original);
+    let lkn = LinkedNode::new(&parse1);
+    let parse2 = parse(formatted);
+    let lkn_oth = LinkedNode::new(&parse2);
+    debug!("{:?}", parse1);
+    debug!("{:?}", parse2);
+    tree_difference(&lkn, &lkn_oth).map(|(node, other, why)| {
+        let (line, column) = utils::line_column(original, node.offset);
+        let (other_line, other_column) = utils::line_column(formatted, other.offset);
+        format!(
+            "{why}: {:?} {:?} at {line}:{column} in the input, {:?} {:?} at {other_line}:{other_column} in the output",
+            node.kind, node.text, other.kind, other.text,
+        )
+    })
+}
+
+/// the node where the trees differ.
+struct Culprit {
+    offset: usize,
+    kind: SyntaxKind,
+    text: String,
+}
+
+fn culprit(node: &LinkedNode) -> Culprit {
+    Culprit {
+        offset: node.offset(),
+        kind: node.kind(),
+        text: node.text().to_string(),
+    }
+}
+
+/// the text of the children of `node`, with whitespace collapsed and the other children as
+/// placeholders, so that rewrapping doesn't change it but changing a word does.
+fn words(node: &LinkedNode) -> String {
+    let mut text = String::new();
+    for child in node.children() {
+        match child.kind() {
+            Text => text.push_str(child.text()),
+            Space | Parbreak | Comma => text.push(' '),
+            _ => text.push_str(" \u{fffc} "),
+        }
+    }
+    text.split_whitespace().join(" ")
+}
+
+// allowing modifying trailing comma's, space everywhere and rewrapping text in markup.
+fn tree_difference(
+    node: &LinkedNode,
+    other_node: &LinkedNode,
+) -> Option<(Culprit, Culprit, &'static str)> {
+    let should_ignore = |x: &LinkedNode| [Space, Parbreak, Comma, Text].contains(&x.kind());
+
+    let node_kind = node.kind();
+    let other_kind = other_node.kind();
+    if node_kind != other_kind {
+        debug!("kind differs! {:?}-{:?}", node_kind, other_kind);
+        return Some((culprit(node), culprit(other_node), "kind differs"));
+    }
+
+    if (node.text() != other_node.text()) && !should_ignore(node) {
+        debug!(
+            "kind ok {:?}\ntext differ:{:?}-{:?}",
+            node.kind(),
+            node.text(),
+            other_node.text()
+        );
+        return Some((culprit(node), culprit(other_node), "text differs"));
+    }
+
+    let (node_words, other_words) = (words(node), words(other_node));
+    if node_words != other_words {
+        debug!("words differ:{node_words:?}-{other_words:?}");
+        let culprit = |node: &LinkedNode, text| Culprit {
+            offset: node.offset(),
+            kind: node.kind(),
+            text,
+        };
+        return Some((
+            culprit(node, node_words),
+            culprit(other_node, other_words),
+            "text differs",
+        ));
+    }
+
+    let fchildren = node.children().filter(|x| !should_ignore(x)).collect_vec();
+    let fchildren_oth = other_node
+        .children()
+        .filter(|x| !should_ignore(x))
+        .collect_vec();
+    if fchildren.len() != fchildren_oth.len() {
+        debug!(
+            "children count differ! {:?}\n{:?}",
+            fchildren, fchildren_oth
+        );
+        return Some((
+            culprit(node),
+            culprit(other_node),
+            "number of children differs",
+        ));
+    }
+    fchildren
+        .iter()
+        .zip(fchildren_oth.iter())
+        .find_map(|(c, oth)| tree_difference(c, oth))
+}
@@ -0,0 +1,183 @@
+//! Finding the typst files of a directory.
+use std::path::{Path, PathBuf};
+
+use super::*;
+use crate::discovery::{absolute, is_root};
+
+/// ignore files, in the syntax of `.gitignore`, a later one wins over an earlier one.
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".typstfmtignore"];
+
+/// Finds the `*.typ` files in `dir` and its subdirectories, sorted.
+///
+/// Files and directories ignored by a `.gitignore` or `.typstfmtignore` (in `dir`, its
+/// subdirectories or its parents up to the root of the project) or excluded by the `exclude`
+/// globs of their configuration file are skipped.
+pub fn typst_files(dir: &Path, resolver: &mut ConfigResolver) -> Result<Vec<PathBuf>, WalkError> {
+    let mut ignores = vec![];
+    let absolute = absolute(dir);
+    // the ignore files of the parents apply too, the root one first.
+    if !is_root(&absolute) {
+        let mut parents = vec![];
+        for parent in absolute.ancestors().skip(1) {
+            parents.push(parent);
+            if is_root(parent) {
+                break;
+            }
+        }
+        for parent in parents.into_iter().rev() {
+            ignores.extend(IgnoreFile::read_all(parent)?);
+        }
+    }
+    let mut files = vec![];
+    walk(dir, &absolute, &mut ignores, resolver, &mut files)?;
+    Ok(files)
+}
+
+/// Tells if the file at `path` would be skipped when searching a directory containing it, because
+/// it or one of its parents is ignored or excluded, see [typst_files].
+///
+/// The file doesn't have to exist, the ignore files are read from its parents up to the root of
+/// the project.
+pub fn is_skipped(path: &Path, resolver: &mut ConfigResolver) -> Result<bool, WalkError> {
+    let absolute = absolute(path);
+    let mut parents = vec![];
+    for parent in absolute.ancestors().skip(1) {
+        parents.push(parent);
+        if is_root(parent) {
+            break;
+        }
+    }
+    // from the root down to the file, like the search would.
+    let mut ignores = vec![];
+    let mut chain = parents.into_iter().rev().chain([absolute.as_path()]);
+    if let Some(root) = chain.next() {
+        ignores.extend(IgnoreFile::read_all(root)?);
+    }
+    for entry in chain {
+        let is_dir = entry != absolute;
+        if (is_dir && entry.file_name().is_some_and(|name| name == ".git"))
+            || is_ignored(&ignores, entry, is_dir)
+            || resolver.is_excluded(entry)?
+        {
+            return Ok(true);
+        }
+        if is_dir {
+            ignores.extend(IgnoreFile::read_all(entry)?);
+        }
+    }
+    Ok(false)
+}
+
+fn walk(
+    dir: &Path,
+    absolute: &Path,
+    ignores: &mut Vec<IgnoreFile>,
+    resolver: &mut ConfigResolver,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), WalkError> {
+    let ignores_len = ignores.len();
+    ignores.extend(IgnoreFile::read_all(absolute)?);
+
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| WalkError::Io(dir.to_path_buf(), err))?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let path = dir.join(&name);
+        let absolute = absolute.join(&name);
+        let is_dir = path.is_dir();
+        if (is_dir && name == ".git")
+            || is_ignored(ignores, &absolute, is_dir)
+            || resolver.is_excluded(&path)?
+        {
+            debug!("skipped {path:?}");
+            continue;
+        }
+        if is_dir {
+            walk(&path, &absolute, ignores, resolver, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "typ") {
+            files.push(path);
+        }
+    }
+
+    ignores.truncate(ignores_len);
+    Ok(())
+}
+
+/// the last rule matching a path decides if it is ignored.
+fn is_ignored(ignores: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for file in ignores {
+        let Ok(relative) = path.strip_prefix(&file.dir) else {
+            continue;
+        };
+        for rule in &file.rules {
+            if (is_dir || !rule.dir_only) && (rule.matches)(relative) {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
+
+struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+/// a line of an ignore file.
+struct Rule {
+    matches: Box<dyn Fn(&Path) -> bool>,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreFile {
+    /// the ignore files of `dir`.
+    fn read_all(dir: &Path) -> Result<Vec<IgnoreFile>, WalkError> {
+        let mut res = vec![];
+        for name in IGNORE_FILE_NAMES {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let content =
+                std::fs::read_to_string(&path).map_err(|err| WalkError::Io(path.clone(), err))?;
+            res.push(IgnoreFile {
+                dir: dir.to_path_buf(),
+                rules: content.lines().filter_map(Rule::parse).collect(),
+            });
+        }
+        Ok(res)
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // a pattern with a slash is relative to the ignore file, it matches at any depth otherwise.
+        let glob = match line.strip_prefix('/') {
+            Some(line) => line.to_string(),
+            None if line.contains('/') => line.to_string(),
+            None => format!("**/{line}"),
+        };
+        let matcher = globmatch::Builder::new(&glob).build_glob().ok()?.matcher;
+        Some(Rule {
+            matches: Box::new(move |path| matcher.is_match(path)),
+            negated,
+            dir_only,
+        })
+    }
+}
@@ -0,0 +1,396 @@
+//! Tests of the command line, running typstfmt on files in temporary directories.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// an empty project in the temporary directory, unique to a test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("typstfmt-cli-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    // the root of the project, the configuration isn't searched above it.
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    dir
+}
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+/// what typstfmt printed and its exit code.
+struct Run {
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+/// runs typstfmt in `dir` with `stdin`, ignoring the global configuration of the user.
+fn typstfmt(dir: &Path, args: &[&str], stdin: &str) -> Run {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_typstfmt"))
+        .current_dir(dir)
+        .args(args)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // typstfmt exits without reading stdin when it refuses its arguments.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    Run {
+        code: output.status.code().unwrap(),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+#[test]
+fn lines_with_a_parse_error() {
+    let dir = temp_dir("lines_error");
+    let file = dir.join("a.typ");
+    write(&file, "#f(a,b)\n#g(c,\n");
+    let run = typstfmt(&dir, &["--lines", "2:2", "a.typ"], "");
+    assert_eq!(run.code, 2, "{}", run.stderr);
+    assert!(
+        run.stderr.contains("a.typ: could not parse input"),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(read(&file), "#f(a,b)\n#g(c,\n");
+    assert_eq!(run.stdout, "");
+
+    let run = typstfmt(&dir, &["--lines", "1:1", "a.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&file), "#f(a, b)\n#g(c,\n");
+}
+
+#[test]
+fn lines_failing_verification() {
+    let dir = temp_dir("lines_verify");
+    let file = dir.join("a.typ");
+    let content = "#{\n  if a { b } // c\n  else { d }\n}\n";
+    write(&file, content);
+    let run = typstfmt(&dir, &["--verify", "--lines", "1:4", "a.typ"], "");
+    assert_eq!(run.code, 3, "{}", run.stderr);
+    assert!(
+        run.stderr.contains("a.typ: formatting would change"),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(read(&file), content);
+}
+
+/// runs git in `dir`, without depending on the user's configuration.
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "user.name=typstfmt",
+            "-c",
+            "user.email=typstfmt@example.com",
+        ])
+        .args(["-c", "commit.gpgsign=false"])
+        .args(args)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?}");
+}
+
+#[test]
+fn changed_since_reports_errors_in_changed_lines() {
+    let dir = temp_dir("changed_since_errors");
+    fs::remove_dir_all(dir.join(".git")).unwrap();
+    git(&dir, &["init", "-q"]);
+    let file = dir.join("a.typ");
+    write(&file, "#f(a,b)\n#g(c,d)\n");
+    git(&dir, &["add", "a.typ"]);
+    git(&dir, &["commit", "-q", "-m", "init"]);
+
+    write(&file, "#f(a,b)\n#g(c,\n");
+    let run = typstfmt(&dir, &["--changed-since", "HEAD", "a.typ"], "");
+    assert_eq!(run.code, 2, "{}", run.stderr);
+    assert!(
+        run.stderr.contains("a.typ: could not parse input"),
+        "{}",
+        run.stderr
+    );
+
+    write(&file, "#f(a,b,x)\n#g(c,d)\n");
+    let run = typstfmt(&dir, &["--changed-since", "HEAD", "a.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&file), "#f(a, b, x)\n#g(c,d)\n");
+}
+
+#[test]
+fn jobs_dont_change_the_output() {
+    let dir = temp_dir("jobs");
+    let files = (0..12).map(|i| format!("{i:02}.typ")).collect::<Vec<_>>();
+    let run = |jobs: &str, mode: &str| {
+        // the files are written again as formatting them in place changes them.
+        for (i, file) in files.iter().enumerate() {
+            let content = if i == 5 || i == 9 {
+                "#f(a,\n".to_string()
+            } else {
+                format!("#f{i}(a,b)\n")
+            };
+            write(&dir.join(file), &content);
+        }
+        let mut args = vec!["-j", jobs, mode];
+        args.extend(files.iter().map(String::as_str));
+        typstfmt(&dir, &args, "")
+    };
+    for mode in ["--check", "--diff", "--verbose"] {
+        let sequential = run("1", mode);
+        let parallel = run("8", mode);
+        assert_eq!(sequential.stdout, parallel.stdout, "{mode}");
+        assert_eq!(sequential.stderr, parallel.stderr, "{mode}");
+        assert_eq!(sequential.code, parallel.code, "{mode}");
+        assert_eq!(sequential.code, 2, "{mode}");
+        let first = sequential.stderr.find("05.typ: could not parse input");
+        let second = sequential.stderr.find("09.typ: could not parse input");
+        assert!(first.unwrap() < second.unwrap(), "{}", sequential.stderr);
+    }
+    // the verbose runs formatted the files in place.
+    assert_eq!(read(&dir.join("11.typ")), "#f11(a, b)\n");
+}
+
+#[test]
+fn unreadable_input_reported_and_skipped() {
+    let dir = temp_dir("unreadable");
+    write(&dir.join("a.typ"), "#f(a,b)\n");
+    write(&dir.join("c.typ"), "#g(c,d)\n");
+    fs::write(dir.join("b.typ"), b"#h(\xff)\n").unwrap();
+    let run = typstfmt(&dir, &["a.typ", "missing.typ", "b.typ", "c.typ"], "");
+    assert_eq!(run.code, 4, "{}", run.stderr);
+    assert!(
+        run.stderr.contains("couldn't read missing.typ"),
+        "{}",
+        run.stderr
+    );
+    assert!(
+        run.stderr
+            .contains("couldn't read b.typ: it isn't valid UTF-8"),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(read(&dir.join("a.typ")), "#f(a, b)\n");
+    assert_eq!(read(&dir.join("c.typ")), "#g(c, d)\n");
+}
+
+#[test]
+fn io_errors_outrank_formatting_needs() {
+    let dir = temp_dir("io_exit");
+    write(&dir.join("a.typ"), "#f(a,b)\n");
+    let run = typstfmt(&dir, &["--check", "a.typ", "missing.typ"], "");
+    assert_eq!(run.code, 4, "{}", run.stderr);
+}
+
+/// the temporary files typstfmt left in `dir`.
+fn temporary_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".typstfmt.tmp"))
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn overwriting_keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("permissions");
+    let file = dir.join("a.typ");
+    write(&file, "#f(a,b)\n");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    let run = typstfmt(&dir, &["a.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&file), "#f(a, b)\n");
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    assert_eq!(temporary_files(&dir), Vec::<PathBuf>::new());
+}
+
+#[cfg(unix)]
+#[test]
+fn overwriting_through_a_symlink() {
+    let dir = temp_dir("symlink");
+    let target = dir.join("sub/target.typ");
+    write(&target, "#f(a,b)\n");
+    std::os::unix::fs::symlink("sub/target.typ", dir.join("link.typ")).unwrap();
+    let run = typstfmt(&dir, &["link.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&target), "#f(a, b)\n");
+    let link = fs::symlink_metadata(dir.join("link.typ")).unwrap();
+    assert!(link.file_type().is_symlink());
+    assert_eq!(temporary_files(&dir.join("sub")), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn read_only_file_refused() {
+    let dir = temp_dir("read_only");
+    let file = dir.join("a.typ");
+    write(&file, "#f(a,b)\n");
+    let mut permissions = fs::metadata(&file).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&file, permissions).unwrap();
+    let run = typstfmt(&dir, &["a.typ"], "");
+    assert_eq!(run.code, 4, "{}", run.stderr);
+    assert!(
+        run.stderr.contains("couldn't write a.typ"),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(read(&file), "#f(a,b)\n");
+    assert_eq!(temporary_files(&dir), Vec::<PathBuf>::new());
+}
+
+#[cfg(unix)]
+#[test]
+fn failed_write_leaves_no_temporary_file() {
+    let dir = temp_dir("failed_write");
+    // creating the temporary file in a read-only directory fails.
+    let sub = dir.join("sub");
+    write(&sub.join("a.typ"), "#f(a,b)\n");
+    let mut permissions = fs::metadata(&sub).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&sub, permissions).unwrap();
+    let run = typstfmt(&dir, &["sub/a.typ"], "");
+    let mut permissions = fs::metadata(&sub).unwrap().permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(&sub, permissions).unwrap();
+    if run.code == 0 {
+        // running as root, the directory is writable anyway.
+        return;
+    }
+    assert_eq!(run.code, 4, "{}", run.stderr);
+    assert_eq!(read(&sub.join("a.typ")), "#f(a,b)\n");
+    assert_eq!(temporary_files(&sub), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn backup_keeps_the_original() {
+    let dir = temp_dir("backup");
+    write(&dir.join("a.typ"), "#f(a,b)\n");
+    write(&dir.join("b.typ"), "#g(c,d)\n");
+    let run = typstfmt(&dir, &["--backup", "a.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&dir.join("a.typ~")), "#f(a,b)\n");
+    let run = typstfmt(&dir, &["--backup=.orig", "b.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&dir.join("b.typ.orig")), "#g(c,d)\n");
+    assert_eq!(read(&dir.join("b.typ")), "#g(c, d)\n");
+    assert_eq!(temporary_files(&dir), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn backup_refused_when_not_overwriting() {
+    let dir = temp_dir("backup_refused");
+    write(&dir.join("a.typ"), "#f(a,b)\n");
+    for args in [
+        &["--backup", "--check", "a.typ"][..],
+        &["--backup", "--diff", "a.typ"],
+        &["--backup", "-o", "out.typ", "a.typ"],
+        &["--backup"],
+    ] {
+        let run = typstfmt(&dir, args, "#f(a,b)\n");
+        assert_eq!(run.code, 6, "{args:?}: {}", run.stderr);
+        assert!(
+            run.stderr.contains("--backup only applies"),
+            "{}",
+            run.stderr
+        );
+    }
+    assert!(!dir.join("a.typ~").exists());
+    assert!(!dir.join("out.typ").exists());
+}
+
+#[test]
+fn stdin_filepath_finds_the_configuration() {
+    let dir = temp_dir("stdin_filepath");
+    write(&dir.join("book/typstfmt.toml"), "indent_space = 4\n");
+    write(&dir.join("book/.typstfmtignore"), "vendor/\n");
+    let input = "#{\nlet x=(1,2)\n}\n";
+    let run = typstfmt(&dir, &["--stdin-filepath", "book/chapter.typ"], input);
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(run.stdout, "#{\n    let x = (1, 2)\n}\n");
+    assert!(!dir.join("book/chapter.typ").exists());
+
+    let run = typstfmt(&dir, &["--stdin-filepath", "book/vendor/lib.typ"], input);
+    assert_eq!(run.stdout, input);
+
+    let run = typstfmt(&dir, &["--stdin-filepath", "chapter.typ"], input);
+    assert_eq!(run.stdout, "#{\n  let x = (1, 2)\n}\n");
+}
+
+#[test]
+fn stdin_filepath_in_diagnostics() {
+    let dir = temp_dir("stdin_filepath_errors");
+    let run = typstfmt(&dir, &["--stdin-filepath", "book/chapter.typ"], "#f(a,\n");
+    assert_eq!(run.code, 2, "{}", run.stderr);
+    assert!(
+        run.stderr
+            .starts_with("book/chapter.typ: could not parse input"),
+        "{}",
+        run.stderr
+    );
+    let run = typstfmt(
+        &dir,
+        &["--stdin-filepath", "book/chapter.typ", "--diff"],
+        "#f(a,b)\n",
+    );
+    assert!(
+        run.stdout.starts_with("--- a/book/chapter.typ\n"),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+fn parent_relative_input_configured_from_its_ancestors() {
+    let dir = temp_dir("parent_relative");
+    write(
+        &dir.join("typstfmt.toml"),
+        "[[overrides]]\nfiles = [\"project/other/*.typ\"]\nindent_space = 4\n",
+    );
+    // a sibling of the input's directory, its configuration doesn't apply.
+    write(&dir.join("project/sub/typstfmt.toml"), "indent_space = 8\n");
+    let file = dir.join("project/other/a.typ");
+    write(&file, "#{\nlet x=1\n}\n");
+    let run = typstfmt(&dir.join("project/sub"), &["../other/a.typ"], "");
+    assert_eq!(run.code, 0, "{}", run.stderr);
+    assert_eq!(read(&file), "#{\n    let x = 1\n}\n");
+}
+
+#[test]
+fn output_with_several_inputs_refused() {
+    let dir = temp_dir("output_several");
+    write(&dir.join("sub/a.typ"), "#f(a,b)\n");
+    write(&dir.join("sub/b.typ"), "#g(c,d)\n");
+    let run = typstfmt(&dir, &["sub", "-o", "out.typ"], "");
+    assert_eq!(run.code, 6, "{}", run.stderr);
+    assert!(
+        run.stderr
+            .contains("--output takes a single input, not 2 files"),
+        "{}",
+        run.stderr
+    );
+    assert!(!dir.join("out.typ").exists());
+    assert_eq!(read(&dir.join("sub/a.typ")), "#f(a,b)\n");
+
+    let run = typstfmt(&dir, &["--unknown", "sub"], "");
+    assert_eq!(run.code, 6, "{}", run.stderr);
+}